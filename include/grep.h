@@ -0,0 +1,43 @@
+/* C API of the grep library, built by
+ * `cargo rustc --lib --features ffi --crate-type cdylib`. */
+
+#ifndef GREP_H
+#define GREP_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A compiled pattern. */
+typedef struct GrepPattern GrepPattern;
+
+/* Receives a matching line, without its terminator, which is only valid
+ * during the call. Returns whether to keep searching. */
+typedef bool (*GrepMatchCallback)(uint64_t line_number, const uint8_t *line,
+                                  size_t len, void *user_data);
+
+/* Compiles a UTF-8 regular expression, or returns NULL when it is invalid. */
+GrepPattern *grep_pattern_new(const char *pattern);
+
+/* Frees a pattern. NULL is ignored. */
+void grep_pattern_free(GrepPattern *pattern);
+
+/* Searches a buffer, returning the number of matching lines passed to the
+ * callback, or -1 on error. */
+int64_t grep_search_buffer(const GrepPattern *pattern, const uint8_t *buf,
+                           size_t len, GrepMatchCallback callback,
+                           void *user_data);
+
+/* Searches a file like grep_search_buffer. */
+int64_t grep_search_path(const GrepPattern *pattern, const char *path,
+                         GrepMatchCallback callback, void *user_data);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
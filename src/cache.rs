@@ -0,0 +1,48 @@
+//! A map holding at most a fixed number of entries, for the state the server
+//! keeps warm between requests. Once full, the least recently used entry
+//! makes room for the next one.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+pub struct Cache<K, V> {
+    capacity: usize,
+    /// Each entry along with the tick at which it was last used.
+    entries: HashMap<K, (V, u64)>,
+    tick: u64,
+}
+
+impl<K: Clone + Eq + Hash, V> Cache<K, V> {
+    pub fn new(capacity: usize) -> Cache<K, V> {
+        Cache {
+            capacity,
+            entries: HashMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// Returns the entry for `key`, marking it as used.
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        self.tick += 1;
+        let (value, used) = self.entries.get_mut(key)?;
+        *used = self.tick;
+        Some(value)
+    }
+
+    /// Adds the entry for `key`, dropping the least recently used one when
+    /// the cache is full.
+    pub fn insert(&mut self, key: K, value: V) {
+        self.tick += 1;
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, (value, self.tick));
+    }
+}
@@ -0,0 +1,68 @@
+//! Checkpoints of long scans, for --checkpoint and --resume.
+//!
+//! The path of every file whose results have been printed is appended to the
+//! checkpoint file, one per line. Paths are recorded in batches, each only
+//! once the results of its files have been flushed, so that a scan resumed
+//! after an interruption neither loses nor repeats results.
+
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::time::{Duration, Instant};
+
+/// How often the files completed are recorded.
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// A checkpoint file being written.
+pub struct Checkpoint {
+    file: File,
+    /// The files completed since the last record.
+    pending: Vec<String>,
+    last_record: Instant,
+}
+
+impl Checkpoint {
+    /// Appends the files completed from now on to the file at `path`,
+    /// creating it if needed.
+    pub fn open(path: &str) -> io::Result<Checkpoint> {
+        Ok(Checkpoint {
+            file: OpenOptions::new().create(true).append(true).open(path)?,
+            pending: Vec::new(),
+            last_record: Instant::now(),
+        })
+    }
+
+    /// Notes that the results of `path` were printed to `out`, and records
+    /// the files completed so far once enough time has passed.
+    pub fn complete(&mut self, out: &mut impl Write, path: &str) -> io::Result<()> {
+        self.pending.push(path.to_string());
+        if self.last_record.elapsed() >= INTERVAL {
+            self.record(out)?;
+        }
+        Ok(())
+    }
+
+    /// Flushes `out` and records the files whose results it held.
+    pub fn record(&mut self, out: &mut impl Write) -> io::Result<()> {
+        out.flush()?;
+        let mut lines = String::new();
+        for path in self.pending.drain(..) {
+            lines.push_str(&path);
+            lines.push('\n');
+        }
+        self.file.write_all(lines.as_bytes())?;
+        self.file.sync_data()?;
+        self.last_record = Instant::now();
+        Ok(())
+    }
+}
+
+/// Returns the files recorded as completed in the checkpoint file at `path`,
+/// none when it does not exist yet.
+pub fn completed(path: &str) -> io::Result<HashSet<String>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(contents.lines().map(String::from).collect()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(HashSet::new()),
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,94 @@
+//! Default options read from a `.rustlerc` file, found in the current
+//! directory or the nearest of its parents, such as the root of a project,
+//! and from the user's `$XDG_CONFIG_HOME/rustle/config`, or
+//! `~/.config/rustle/config` without `XDG_CONFIG_HOME`.
+//!
+//! Each line of a file holds one argument as given on the command line,
+//! such as `--no-ignore-vcs`, or `--context` followed by its value on the
+//! next line. Blank lines and lines starting with `#` are skipped. The
+//! arguments of the `.rustlerc` come first, then those of the user's file,
+//! then those given on the command line, so that an option taking a single
+//! value takes it from the last of them to give it. Options that can be
+//! given several times, such as --pattern or --file, add up instead, keeping
+//! the values of the files along with those of the command line. --no-config
+//! leaves both files out.
+
+use crate::messages;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The name of the file holding the default options of a project.
+const FILE_NAME: &str = ".rustlerc";
+
+/// The path of the user's file holding the default options, within their
+/// config directory.
+const USER_FILE: &str = "rustle/config";
+
+/// The commands that take the options of a search, after which the
+/// default options are inserted.
+const SEARCH_COMMANDS: [&str; 2] = ["search", "replace"];
+
+/// The commands that take other options, which are left alone.
+const OTHER_COMMANDS: [&str; 3] = ["files", "types", "help"];
+
+/// Returns the arguments the program was run with, along with the default
+/// options of the nearest `.rustlerc` and of the user's config file.
+pub fn args() -> Vec<OsString> {
+    let mut args: Vec<OsString> = env::args_os().collect();
+    let no_config = args
+        .iter()
+        .skip(1)
+        .take_while(|arg| *arg != "--")
+        .any(|arg| arg == "--no-config");
+    let command = args.get(1).and_then(|arg| arg.to_str());
+    if no_config || command.is_some_and(|command| OTHER_COMMANDS.contains(&command)) {
+        return args;
+    }
+    let at = match command {
+        Some(command) if SEARCH_COMMANDS.contains(&command) => 2,
+        _ => 1,
+    };
+
+    let project = env::current_dir().ok().and_then(|dir| find(&dir));
+    let mut defaults = Vec::new();
+    for path in project.into_iter().chain(user_file()) {
+        match read(&path) {
+            Ok(options) => defaults.extend(options),
+            // Whether to report errors is not known until the arguments are
+            // parsed.
+            Err(e) => messages::report(&format!("{}: {e}", path.display()), false),
+        }
+    }
+    args.splice(at..at, defaults);
+    args
+}
+
+/// Returns the user's config file, when there is one.
+fn user_file() -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join(USER_FILE)).filter(|path| path.is_file())
+}
+
+/// Returns the `.rustlerc` in `dir` or the nearest of its parents.
+fn find(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// Reads the arguments held in the file at `path`.
+fn read(path: &Path) -> io::Result<Vec<OsString>> {
+    let text = fs::read_to_string(path)?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(OsString::from)
+        .collect())
+}
@@ -0,0 +1,200 @@
+//! Parsing of CSV and TSV files for --csv.
+//!
+//! Fields are separated by a delimiter and rows by newlines. A field starting
+//! with a double quote runs up to the matching closing quote, and may hold
+//! delimiters, newlines and doubled quotes standing for one, as in RFC 4180.
+//! A row may therefore span several lines of the file.
+
+use std::mem;
+
+/// A row of a CSV file.
+pub struct Row<'a> {
+    /// The number of the line the row starts on.
+    pub number: usize,
+    /// The offset in bytes of the start of the row.
+    pub offset: usize,
+    /// The text of the row as it is in the file, without its terminator.
+    pub text: &'a str,
+    /// The value of each field, with its quotes removed.
+    pub fields: Vec<String>,
+}
+
+/// Parses the rows of `text`, whose fields are separated by `delimiter`.
+/// Blank lines are skipped.
+pub fn parse(text: &str, delimiter: char) -> Result<Vec<Row<'_>>, String> {
+    let mut rows = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    // Only a quote opening a field starts a quoted field. Quotes elsewhere
+    // are taken literally.
+    let mut at_field_start = true;
+    let mut number = 1;
+    let mut row_number = 1;
+    let mut row_start = 0;
+
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '\n' {
+            number += 1;
+        }
+        if quoted {
+            match c {
+                '"' if chars.next_if(|&(_, c)| c == '"').is_some() => field.push('"'),
+                '"' => quoted = false,
+                c => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if at_field_start => {
+                quoted = true;
+                at_field_start = false;
+            }
+            c if c == delimiter => {
+                fields.push(mem::take(&mut field));
+                at_field_start = true;
+            }
+            '\n' => {
+                let end = if text[..i].ends_with('\r') { i - 1 } else { i };
+                if field.ends_with('\r') {
+                    field.pop();
+                }
+                fields.push(mem::take(&mut field));
+                if row_start < end {
+                    rows.push(Row {
+                        number: row_number,
+                        offset: row_start,
+                        text: &text[row_start..end],
+                        fields: mem::take(&mut fields),
+                    });
+                }
+                fields.clear();
+                at_field_start = true;
+                row_start = i + 1;
+                row_number = number;
+            }
+            c => {
+                field.push(c);
+                at_field_start = false;
+            }
+        }
+    }
+    if quoted {
+        return Err(format!("line {row_number}: unterminated quoted field"));
+    }
+    if row_start < text.len() {
+        fields.push(field);
+        rows.push(Row {
+            number: row_number,
+            offset: row_start,
+            text: &text[row_start..],
+            fields,
+        });
+    }
+    Ok(rows)
+}
+
+/// Returns the indices of the `columns`, each given by its name in the
+/// `header` or by its number counted from 1.
+pub fn column_indices(header: &Row, columns: &[String]) -> Result<Vec<usize>, String> {
+    columns
+        .iter()
+        .map(|column| {
+            if let Some(i) = header.fields.iter().position(|name| name == column) {
+                return Ok(i);
+            }
+            match column.parse::<usize>() {
+                Ok(n) if n >= 1 => Ok(n - 1),
+                _ => Err(format!("no column named '{column}'")),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the fields of each row of `text`.
+    fn fields(text: &str, delimiter: char) -> Vec<Vec<String>> {
+        let rows = parse(text, delimiter).unwrap();
+        rows.into_iter().map(|row| row.fields).collect()
+    }
+
+    #[test]
+    fn plain_fields() {
+        assert_eq!(
+            fields("a,b,c\n1,2,3\n", ','),
+            [["a", "b", "c"], ["1", "2", "3"]]
+        );
+        assert_eq!(fields("a\tb,c\n", '\t'), [["a", "b,c"]]);
+        assert_eq!(fields(",x,\n", ','), [["", "x", ""]]);
+    }
+
+    #[test]
+    fn quoted_fields() {
+        assert_eq!(fields("\"a,b\",c\n", ','), [["a,b", "c"]]);
+        assert_eq!(fields("\"say \"\"hi\"\"\",x\n", ','), [["say \"hi\"", "x"]]);
+        assert_eq!(fields("\"\",x\n", ','), [["", "x"]]);
+        // A quote within an unquoted field is taken literally.
+        assert_eq!(fields("5\" pipe,x\n", ','), [["5\" pipe", "x"]]);
+    }
+
+    #[test]
+    fn multi_line_rows() {
+        let rows = parse("id,note\n1,\"two\nlines\"\n2,one\n", ',').unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1].fields, ["1", "two\nlines"]);
+        assert_eq!(rows[1].text, "1,\"two\nlines\"");
+        assert_eq!((rows[1].number, rows[1].offset), (2, 8));
+        assert_eq!((rows[2].number, rows[2].offset), (4, 22));
+    }
+
+    #[test]
+    fn crlf() {
+        let rows = parse("a,b\r\n\"c\",d\r\n", ',').unwrap();
+        assert_eq!(rows[0].fields, ["a", "b"]);
+        assert_eq!(rows[0].text, "a,b");
+        assert_eq!(rows[1].fields, ["c", "d"]);
+        assert_eq!(rows[1].text, "\"c\",d");
+    }
+
+    #[test]
+    fn blank_lines_are_skipped() {
+        let rows = parse("a\n\n\r\nb\n", ',').unwrap();
+        let numbers: Vec<usize> = rows.iter().map(|row| row.number).collect();
+        assert_eq!(numbers, [1, 4]);
+        assert_eq!(rows[1].fields, ["b"]);
+    }
+
+    #[test]
+    fn last_row_without_newline() {
+        let rows = parse("a,b\nc,d", ',').unwrap();
+        assert_eq!(rows[1].fields, ["c", "d"]);
+        assert_eq!(rows[1].text, "c,d");
+    }
+
+    #[test]
+    fn unterminated_quote() {
+        assert_eq!(
+            parse("a\nb,\"c\nd\n", ',').err().as_deref(),
+            Some("line 2: unterminated quoted field")
+        );
+    }
+
+    #[test]
+    fn column_indices_by_name_or_number() {
+        let rows = parse("name,email,2\n", ',').unwrap();
+        let columns = |columns: &[&str]| {
+            let columns: Vec<String> = columns.iter().map(|c| c.to_string()).collect();
+            column_indices(&rows[0], &columns)
+        };
+        assert_eq!(columns(&["email", "name"]), Ok(vec![1, 0]));
+        assert_eq!(columns(&["1", "3"]), Ok(vec![0, 2]));
+        // A name takes precedence over a number.
+        assert_eq!(columns(&["2"]), Ok(vec![2]));
+        assert!(columns(&["0"]).is_err());
+        assert!(columns(&["phone"]).is_err());
+    }
+}
@@ -0,0 +1,373 @@
+//! Decoders converting rich document formats to plain text before searching.
+//!
+//! Each format is enabled by the cargo feature of the same name (`pdf`, `docx`
+//! and `epub`, or all of them with `decoders`). A decoder is selected by the
+//! extension of the file or, failing that, by the magic bytes at its start.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// A decoder for a document format.
+pub struct Decoder {
+    /// Extensions, without the leading dot, of files in this format.
+    extensions: &'static [&'static str],
+    /// Returns whether the start of a file identifies it as this format.
+    magic: fn(&[u8]) -> bool,
+    /// Converts the contents of a file to text.
+    decode: fn(&[u8]) -> Result<String, String>,
+}
+
+impl Decoder {
+    /// Reads the rest of `f` and converts it to text.
+    pub fn decode(&self, f: &mut impl Read) -> Result<String, String> {
+        let mut bytes = Vec::new();
+        f.read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+        (self.decode)(&bytes)
+    }
+}
+
+/// The decoders enabled in this build.
+const DECODERS: &[Decoder] = &[
+    #[cfg(feature = "pdf")]
+    Decoder {
+        extensions: &["pdf"],
+        magic: |header| header.starts_with(b"%PDF-"),
+        decode: pdf::decode,
+    },
+    #[cfg(feature = "docx")]
+    Decoder {
+        extensions: &["docx"],
+        magic: |_| false,
+        decode: docx::decode,
+    },
+    #[cfg(feature = "epub")]
+    Decoder {
+        extensions: &["epub"],
+        magic: |header| header.get(30..58) == Some(b"mimetypeapplication/epub+zip"),
+        decode: epub::decode,
+    },
+];
+
+/// Every document type that can be decoded, by name, along with its
+/// extensions and whether its feature is enabled in this build.
+pub const TYPES: &[(&str, &[&str], bool)] = &[
+    ("pdf", &["pdf"], cfg!(feature = "pdf")),
+    ("docx", &["docx"], cfg!(feature = "docx")),
+    ("epub", &["epub"], cfg!(feature = "epub")),
+];
+
+/// Returns the decoder for the file at `path`, if any. Identifying a file by
+/// its magic bytes reads the start of `f`, which is rewound afterwards.
+pub fn find(path: &str, f: &mut (impl Read + Seek)) -> io::Result<Option<&'static Decoder>> {
+    if DECODERS.is_empty() {
+        return Ok(None);
+    }
+
+    if let Some((_, extension)) = path.rsplit_once('.') {
+        let extension = extension.to_ascii_lowercase();
+        if let Some(decoder) = DECODERS
+            .iter()
+            .find(|d| d.extensions.contains(&extension.as_str()))
+        {
+            return Ok(Some(decoder));
+        }
+    }
+
+    let mut header = Vec::with_capacity(64);
+    f.by_ref().take(64).read_to_end(&mut header)?;
+    f.seek(SeekFrom::Start(0))?;
+
+    Ok(DECODERS.iter().find(|d| (d.magic)(&header)))
+}
+
+/// Extracts the text of an XML document, starting a new line after every
+/// element for which `is_break` returns true.
+#[cfg(any(feature = "docx", feature = "epub"))]
+fn xml_to_text(xml: &str, is_break: fn(&str) -> bool) -> String {
+    let mut text = String::with_capacity(xml.len() / 2);
+    let mut rest = xml;
+    while let Some(open) = rest.find('<') {
+        text.push_str(&unescape(&rest[..open]));
+        let Some(close) = rest[open..].find('>') else {
+            break;
+        };
+
+        let tag = &rest[open + 1..open + close];
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default();
+        let is_end = tag.starts_with('/') || tag.ends_with('/');
+        if is_end && is_break(name) && !text.ends_with('\n') {
+            text.push('\n');
+        }
+
+        rest = &rest[open + close + 1..];
+    }
+    text.push_str(&unescape(rest));
+    text
+}
+
+/// Replaces the predefined XML entities and character references in `s`.
+#[cfg(any(feature = "docx", feature = "epub", feature = "s3"))]
+pub fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        unescaped.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        let Some(semi) = rest.find(';') else {
+            break;
+        };
+        let c = match &rest[1..semi] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            entity => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                unescaped.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// Reads the entry `name` of a ZIP archive as a string.
+#[cfg(any(feature = "docx", feature = "epub"))]
+fn read_zip_entry<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    name: &str,
+) -> Result<String, String> {
+    let mut entry = archive.by_name(name).map_err(|e| e.to_string())?;
+    let mut contents = String::new();
+    entry
+        .read_to_string(&mut contents)
+        .map_err(|e| e.to_string())?;
+    Ok(contents)
+}
+
+#[cfg(feature = "docx")]
+mod docx {
+    use std::io::Cursor;
+
+    /// Extracts the body text of a Word document, one paragraph per line.
+    pub fn decode(bytes: &[u8]) -> Result<String, String> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).map_err(|e| e.to_string())?;
+        let document = super::read_zip_entry(&mut archive, "word/document.xml")?;
+        Ok(super::xml_to_text(&document, |name| {
+            matches!(name, "w:p" | "w:br" | "w:tab")
+        }))
+    }
+}
+
+#[cfg(feature = "epub")]
+mod epub {
+    use std::io::Cursor;
+
+    /// Extracts the text of every (X)HTML document in an EPUB, in the order
+    /// they are stored in the archive.
+    pub fn decode(bytes: &[u8]) -> Result<String, String> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).map_err(|e| e.to_string())?;
+        let names: Vec<String> = archive
+            .file_names()
+            .filter_map(Result::ok)
+            .filter(|name| {
+                name.ends_with(".xhtml") || name.ends_with(".html") || name.ends_with(".htm")
+            })
+            .map(|name| name.into_owned())
+            .collect();
+
+        let mut text = String::new();
+        for name in names {
+            let document = super::read_zip_entry(&mut archive, &name)?;
+            text.push_str(&super::xml_to_text(&document, is_block));
+        }
+        Ok(text)
+    }
+
+    fn is_block(name: &str) -> bool {
+        matches!(
+            name,
+            "p" | "div" | "br" | "li" | "tr" | "title" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6"
+        )
+    }
+}
+
+#[cfg(feature = "pdf")]
+mod pdf {
+    //! A minimal PDF text extractor. It decodes the text shown by the content
+    //! streams of the document, which works for simple fonts but not for
+    //! fonts that need a character map (e.g. most CJK documents).
+
+    use flate2::read::ZlibDecoder;
+    use std::io::Read;
+
+    pub fn decode(bytes: &[u8]) -> Result<String, String> {
+        let mut text = String::new();
+        let mut rest = bytes;
+        while let Some(start) = find(rest, b"stream") {
+            let dictionary = &rest[..start];
+            let mut body = &rest[start + b"stream".len()..];
+            body = body.strip_prefix(b"\r").unwrap_or(body);
+            body = body.strip_prefix(b"\n").unwrap_or(body);
+            let Some(end) = find(body, b"endstream") else {
+                break;
+            };
+
+            // Only the dictionary of the object owning this stream matters.
+            let dictionary = match find_last(dictionary, b"obj") {
+                Some(obj) => &dictionary[obj..],
+                None => dictionary,
+            };
+            let data = &body[..end];
+            if find(dictionary, b"/FlateDecode").is_some() {
+                let mut inflated = Vec::new();
+                // Streams that are not content (or are damaged) are skipped.
+                if ZlibDecoder::new(data).read_to_end(&mut inflated).is_ok() {
+                    extract_text(&inflated, &mut text);
+                }
+            } else if find(dictionary, b"/Filter").is_none() {
+                extract_text(data, &mut text);
+            }
+
+            rest = &body[end + b"endstream".len()..];
+        }
+
+        if text.is_empty() && find(bytes, b"%PDF-").is_none() {
+            return Err("not a PDF document".to_string());
+        }
+        Ok(text)
+    }
+
+    /// Appends the strings shown by the text operators of a content stream to
+    /// `text`, starting a new line whenever the text moves to a new line.
+    fn extract_text(content: &[u8], text: &mut String) {
+        let mut strings: Vec<Vec<u8>> = Vec::new();
+        let mut i = 0;
+        while i < content.len() {
+            match content[i] {
+                b'(' => {
+                    let (string, end) = literal_string(content, i + 1);
+                    strings.push(string);
+                    i = end;
+                }
+                b'%' => {
+                    while i < content.len() && content[i] != b'\n' && content[i] != b'\r' {
+                        i += 1;
+                    }
+                }
+                c if c.is_ascii_alphabetic() || c == b'\'' || c == b'"' || c == b'*' => {
+                    let start = i;
+                    while i < content.len()
+                        && (content[i].is_ascii_alphabetic() || b"'\"*".contains(&content[i]))
+                    {
+                        i += 1;
+                    }
+                    match &content[start..i] {
+                        b"Tj" | b"TJ" => {
+                            for string in strings.drain(..) {
+                                text.extend(string.iter().map(|&b| b as char));
+                            }
+                        }
+                        b"'" | b"\"" => {
+                            new_line(text);
+                            for string in strings.drain(..) {
+                                text.extend(string.iter().map(|&b| b as char));
+                            }
+                        }
+                        b"T*" | b"Td" | b"TD" | b"ET" => new_line(text),
+                        _ => strings.clear(),
+                    }
+                }
+                _ => i += 1,
+            }
+        }
+    }
+
+    fn new_line(text: &mut String) {
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+    }
+
+    /// Parses a literal string starting just after its opening parenthesis.
+    /// Returns the string along with the index just past its closing
+    /// parenthesis.
+    fn literal_string(content: &[u8], mut i: usize) -> (Vec<u8>, usize) {
+        let mut string = Vec::new();
+        let mut depth = 1;
+        while i < content.len() {
+            let c = content[i];
+            i += 1;
+            match c {
+                b'(' => depth += 1,
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                b'\\' if i < content.len() => {
+                    let escaped = content[i];
+                    i += 1;
+                    match escaped {
+                        b'n' => string.push(b'\n'),
+                        b'r' => string.push(b'\r'),
+                        b't' => string.push(b'\t'),
+                        b'b' => string.push(0x08),
+                        b'f' => string.push(0x0c),
+                        b'0'..=b'7' => {
+                            let mut value = u32::from(escaped - b'0');
+                            for _ in 0..2 {
+                                match content.get(i) {
+                                    Some(&d @ b'0'..=b'7') => {
+                                        value = value * 8 + u32::from(d - b'0');
+                                        i += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            string.push(value as u8);
+                        }
+                        // A backslash at the end of a line continues the string.
+                        b'\r' | b'\n' => {
+                            if escaped == b'\r' && content.get(i) == Some(&b'\n') {
+                                i += 1;
+                            }
+                        }
+                        other => string.push(other),
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            string.push(c);
+        }
+        (string, i)
+    }
+
+    fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        memchr::memmem::find(haystack, needle)
+    }
+
+    fn find_last(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        memchr::memmem::rfind(haystack, needle)
+    }
+}
@@ -0,0 +1,83 @@
+//! Diagnostics for patterns that fail to compile.
+
+use regex_syntax::ast::{self, Span};
+use regex_syntax::hir;
+
+/// Explains why `pattern` failed to compile with `error`, pointing at the
+/// offending part of the pattern and suggesting a fix where possible.
+pub fn explain(pattern: &str, error: &regex::Error) -> String {
+    let (span, message, help) = match regex_syntax::Parser::new().parse(pattern) {
+        Err(regex_syntax::Error::Parse(e)) => {
+            (Some(*e.span()), e.kind().to_string(), ast_help(e.kind()))
+        }
+        Err(regex_syntax::Error::Translate(e)) => {
+            (Some(*e.span()), e.kind().to_string(), hir_help(e.kind()))
+        }
+        // The pattern is valid but the compiled regex exceeds a size limit.
+        _ => (
+            None,
+            error.to_string(),
+            Some("simplify the pattern, e.g. by reducing large repetition counts"),
+        ),
+    };
+
+    let mut explanation = format!("error: invalid pattern: {message}\n");
+    if let Some(span) = span {
+        explanation.push_str(&underline(pattern, span));
+    }
+    if let Some(help) = help {
+        explanation.push_str(&format!("help: {help}\n"));
+    }
+    explanation
+}
+
+/// Returns the line of `pattern` containing the start of `span`, with a caret
+/// line underneath pointing at the span.
+fn underline(pattern: &str, span: Span) -> String {
+    let line = pattern.lines().nth(span.start.line - 1).unwrap_or_default();
+    let width = match span.start.line == span.end.line {
+        true => span.end.column.saturating_sub(span.start.column).max(1),
+        false => 1,
+    };
+
+    format!(
+        "  | {line}\n  | {}{}\n",
+        " ".repeat(span.start.column - 1),
+        "^".repeat(width)
+    )
+}
+
+fn ast_help(kind: &ast::ErrorKind) -> Option<&'static str> {
+    use ast::ErrorKind::*;
+
+    Some(match kind {
+        GroupUnclosed => r"add the missing ')', or write \( to match a literal parenthesis",
+        GroupUnopened => r"remove the extra ')', or write \) to match a literal parenthesis",
+        ClassUnclosed => r"add the missing ']', or write \[ to match a literal bracket",
+        RepetitionMissing => r"write \*, \+ or \? to match the character literally",
+        RepetitionCountUnclosed | RepetitionCountInvalid | RepetitionCountDecimalEmpty => {
+            r"write \{ to match a literal brace"
+        }
+        EscapeUnrecognized => r"write \\ to match a literal backslash",
+        ClassRangeInvalid => "write the range with its smaller character first, e.g. [a-z]",
+        UnsupportedLookAround => {
+            "match the surrounding text as well and select the part you need with --only-matching --group"
+        }
+        UnsupportedBackreference => {
+            "spell out the repeated text instead"
+        }
+        FlagUnrecognized => "the supported flags are i, m, s, U, u, R and x",
+        _ => return None,
+    })
+}
+
+fn hir_help(kind: &hir::ErrorKind) -> Option<&'static str> {
+    use hir::ErrorKind::*;
+
+    Some(match kind {
+        UnicodePropertyNotFound | UnicodePropertyValueNotFound => {
+            r"check the spelling of the property, e.g. \p{Greek} or \p{Script=Latin}"
+        }
+        _ => return None,
+    })
+}
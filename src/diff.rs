@@ -0,0 +1,75 @@
+//! Unified diffs of the replacements previewed with --diff.
+//!
+//! The lines selected in a file, the matching lines with their context, are
+//! split into hunks wherever they stop being consecutive. Each matching line
+//! the replacement changes is shown removed and followed by its replacement,
+//! which may span several lines, while the other lines are shown as context.
+
+use crate::printer::Line;
+use std::io::{self, Write};
+
+/// Writes the diff between the `lines` selected from the file at `path` and
+/// the same lines with `replace` applied to the matching ones, colored when
+/// `color` is set. Nothing is written when no line changes.
+pub fn write(
+    out: &mut impl Write,
+    path: &str,
+    lines: &[Line],
+    replace: impl Fn(&str) -> String,
+    color: bool,
+) -> io::Result<()> {
+    let paint = |code: &str, text: &str| match color {
+        true => format!("\x1b[{code}m{text}\x1b[0m"),
+        false => text.to_string(),
+    };
+
+    // The number of lines added by the hunks written so far, less those
+    // removed.
+    let mut delta = 0;
+    let mut started = false;
+    for hunk in lines.chunk_by(|a, b| a.number + 1 == b.number) {
+        let mut changes = Vec::new();
+        for line in hunk {
+            let replaced = line.matched.then(|| replace(&line.text));
+            match replaced.filter(|replaced| *replaced != line.text) {
+                Some(replaced) => {
+                    changes.push(('-', line.text.to_string(), line.terminated));
+                    let mut new_lines = replaced.split('\n').peekable();
+                    while let Some(new_line) = new_lines.next() {
+                        let terminated = line.terminated || new_lines.peek().is_some();
+                        changes.push(('+', new_line.to_string(), terminated));
+                    }
+                }
+                None => changes.push((' ', line.text.to_string(), line.terminated)),
+            }
+        }
+        if changes.iter().all(|&(kind, ..)| kind == ' ') {
+            continue;
+        }
+
+        if !started {
+            writeln!(out, "{}", paint("1", &format!("--- {path}")))?;
+            writeln!(out, "{}", paint("1", &format!("+++ {path}")))?;
+            started = true;
+        }
+        let old_start = hunk[0].number;
+        let old_len = hunk.len();
+        let new_len = changes.iter().filter(|&&(kind, ..)| kind != '-').count();
+        let new_start = old_start as isize + delta;
+        delta += new_len as isize - old_len as isize;
+        let header = format!("@@ -{old_start},{old_len} +{new_start},{new_len} @@");
+        writeln!(out, "{}", paint("36", &header))?;
+        for (kind, text, terminated) in changes {
+            let line = format!("{kind}{text}");
+            match kind {
+                '-' => writeln!(out, "{}", paint("31", &line))?,
+                '+' => writeln!(out, "{}", paint("32", &line))?,
+                _ => writeln!(out, "{line}")?,
+            }
+            if !terminated {
+                writeln!(out, "\\ No newline at end of file")?;
+            }
+        }
+    }
+    Ok(())
+}
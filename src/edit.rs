@@ -0,0 +1,257 @@
+//! Making the replacements in the files themselves, with --write.
+//!
+//! The matching lines of a file are replaced in a temporary file next to
+//! it, which then takes its place, so that the file is never left half
+//! written. A symlink is followed to the file it points to, which keeps its
+//! permissions and ownership, and a file with other hard links is refused,
+//! as taking its place would split it from them. With --backup-suffix or
+//! --backup-dir, the original is first copied aside.
+
+use crate::printer::Line;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::process;
+
+/// Where the original of each edited file is kept.
+pub struct Backup<'a> {
+    /// Appended to the name of the file.
+    pub suffix: Option<&'a str>,
+    /// Holds the originals at the paths of the files below it.
+    pub dir: Option<&'a Path>,
+}
+
+/// Replaces the matching `lines` of the file at `path` by their text with
+/// `replace` applied, keeping a copy of the original as `backup` says. The
+/// file is left alone when no line changes, and fails to be edited when it
+/// is not UTF-8 text, has other hard links, or no longer holds the lines
+/// searched.
+pub fn write(
+    path: &str,
+    lines: &[Line],
+    replace: impl Fn(&str) -> String,
+    backup: &Backup,
+) -> io::Result<()> {
+    // The text each changed line had when searched and its replacement, by
+    // line number.
+    let replaced: HashMap<usize, (&str, String)> = lines
+        .iter()
+        .filter(|line| line.matched)
+        .filter_map(|line| {
+            let new_text = replace(&line.text);
+            (new_text != line.text).then(|| (line.number, (&*line.text, new_text)))
+        })
+        .collect();
+    if replaced.is_empty() {
+        return Ok(());
+    }
+
+    let target = fs::canonicalize(path)?;
+    let metadata = fs::metadata(&target)?;
+    #[cfg(unix)]
+    if std::os::unix::fs::MetadataExt::nlink(&metadata) > 1 {
+        return Err(io::Error::other(
+            "has other hard links, which editing it would break",
+        ));
+    }
+    let original = fs::read(&target)?;
+    let text = String::from_utf8(original)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "not UTF-8 text"))?;
+    let mut edited = String::with_capacity(text.len());
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        let Some((searched, new_text)) = replaced.get(&(i + 1)) else {
+            edited.push_str(line);
+            continue;
+        };
+        // The terminator of the line is kept as it is.
+        let content = line.strip_suffix('\n').unwrap_or(line);
+        let content = content.strip_suffix('\r').unwrap_or(content);
+        if content != *searched {
+            return Err(io::Error::other(format!(
+                "line {} changed since it was searched",
+                i + 1
+            )));
+        }
+        edited.push_str(new_text);
+        edited.push_str(&line[content.len()..]);
+    }
+
+    let path = Path::new(path);
+    if let Some(backup_path) = backup_path(path, backup) {
+        if let Some(parent) = backup_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(&target, &backup_path)?;
+    }
+
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    let temp = target.with_file_name(format!(".{name}.{}.tmp", process::id()));
+    let result = fs::write(&temp, edited)
+        .and_then(|()| set_owner(&temp, &metadata))
+        .and_then(|()| fs::set_permissions(&temp, metadata.permissions()))
+        .and_then(|()| fs::rename(&temp, &target));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Gives the file at `path` the owner and group in `metadata`.
+#[cfg(unix)]
+fn set_owner(path: &Path, metadata: &fs::Metadata) -> io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+    std::os::unix::fs::chown(path, Some(metadata.uid()), Some(metadata.gid()))
+}
+
+#[cfg(not(unix))]
+fn set_owner(_path: &Path, _metadata: &fs::Metadata) -> io::Result<()> {
+    Ok(())
+}
+
+/// Returns where to keep the original of the file at `path`, if anywhere.
+/// In the backup directory, the original is kept at the path of the file,
+/// without its root and any `..`.
+fn backup_path(path: &Path, backup: &Backup) -> Option<PathBuf> {
+    let mut backup_path = match backup.dir {
+        Some(dir) => {
+            let relative = path.components().filter_map(|c| match c {
+                Component::Normal(part) => Some(part),
+                _ => None,
+            });
+            dir.join(relative.collect::<PathBuf>())
+        }
+        None if backup.suffix.is_some() => path.to_path_buf(),
+        None => return None,
+    };
+    if let Some(suffix) = backup.suffix {
+        let mut name = backup_path.file_name()?.to_os_string();
+        name.push(suffix);
+        backup_path.set_file_name(name);
+    }
+    Some(backup_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    /// A fresh directory for a test to edit files in.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rustle-edit-{name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn matched(number: usize, text: &str) -> Line<'_> {
+        Line {
+            number,
+            offset: 0,
+            text: Cow::Borrowed(text),
+            matched: true,
+            terminated: true,
+            header: false,
+        }
+    }
+
+    fn upper(text: &str) -> String {
+        text.to_uppercase()
+    }
+
+    #[test]
+    fn replaces_lines_and_keeps_a_backup() {
+        let dir = temp_dir("backup");
+        let path = dir.join("file.txt");
+        fs::write(&path, "one\ntwo\r\nthree").unwrap();
+        let backup = Backup {
+            suffix: Some(".orig"),
+            dir: None,
+        };
+        let lines = [matched(2, "two"), matched(3, "three")];
+        write(path.to_str().unwrap(), &lines, upper, &backup).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one\nTWO\r\nTHREE");
+        let original = dir.join("file.txt.orig");
+        assert_eq!(fs::read_to_string(original).unwrap(), "one\ntwo\r\nthree");
+        // No temporary file is left behind.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_backups_below_a_directory() {
+        let dir = temp_dir("backup-dir");
+        let path = dir.join("file.txt");
+        fs::write(&path, "one\n").unwrap();
+        let backups = dir.join("backups");
+        let backup = Backup {
+            suffix: None,
+            dir: Some(&backups),
+        };
+        write(path.to_str().unwrap(), &[matched(1, "one")], upper, &backup).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "ONE\n");
+        let original = backup_path(&path, &backup).unwrap();
+        assert!(original.starts_with(&backups));
+        assert_eq!(fs::read_to_string(original).unwrap(), "one\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_lines_changed_since_searched() {
+        let dir = temp_dir("changed");
+        let path = dir.join("file.txt");
+        fs::write(&path, "one\n").unwrap();
+        let backup = Backup {
+            suffix: None,
+            dir: None,
+        };
+        let result = write(path.to_str().unwrap(), &[matched(1, "two")], upper, &backup);
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn edits_the_target_of_a_symlink() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("symlink");
+        let path = dir.join("file.txt");
+        fs::write(&path, "one\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        let link = dir.join("link.txt");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+        let backup = Backup {
+            suffix: None,
+            dir: None,
+        };
+        write(link.to_str().unwrap(), &[matched(1, "one")], upper, &backup).unwrap();
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "ONE\n");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuses_files_with_hard_links() {
+        let dir = temp_dir("hard-link");
+        let path = dir.join("file.txt");
+        fs::write(&path, "one\n").unwrap();
+        fs::hard_link(&path, dir.join("link.txt")).unwrap();
+        let backup = Backup {
+            suffix: None,
+            dir: None,
+        };
+        let result = write(path.to_str().unwrap(), &[matched(1, "one")], upper, &backup);
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(dir.join("link.txt")).unwrap(), "one\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
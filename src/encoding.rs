@@ -0,0 +1,147 @@
+//! Detection and transcoding of text encodings.
+//!
+//! With `--encoding auto`, each file is identified by its byte order mark,
+//! then by the pattern of zero bytes typical of UTF-16, then by whether it is
+//! valid UTF-8. Anything else is decoded with each of a few common legacy
+//! encodings and the one producing the most plausible text is chosen.
+
+use encoding_rs::{
+    Encoding as Charset, BIG5, EUC_JP, EUC_KR, GBK, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8,
+    WINDOWS_1252,
+};
+
+/// How the contents of files are decoded.
+#[derive(Clone, Copy)]
+pub enum Encoding {
+    /// Detect the encoding of each file.
+    Auto,
+    /// Decode every file with the given encoding.
+    Fixed(&'static Charset),
+}
+
+impl Encoding {
+    /// Parses `auto` or an encoding label such as `utf-16le` or `latin1`.
+    pub fn parse(s: &str) -> Result<Encoding, String> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(Encoding::Auto);
+        }
+        Charset::for_label(s.as_bytes())
+            .map(Encoding::Fixed)
+            .ok_or_else(|| format!("unknown encoding '{s}'"))
+    }
+
+    /// Decodes `bytes` to text, replacing malformed sequences with U+FFFD. A
+    /// byte order mark takes precedence over a fixed encoding.
+    pub fn decode(self, bytes: &[u8]) -> String {
+        let charset = match self {
+            Encoding::Auto => detect(bytes),
+            Encoding::Fixed(charset) => charset,
+        };
+        charset.decode(bytes).0.into_owned()
+    }
+}
+
+/// Multi-byte legacy encodings tried when a file is neither UTF-16 nor UTF-8,
+/// in order of preference when they are equally plausible.
+const CANDIDATES: &[&Charset] = &[SHIFT_JIS, EUC_JP, GBK, EUC_KR, BIG5];
+
+/// Number of bytes at the start of a file used to detect its encoding.
+const SAMPLE: usize = 64 * 1024;
+
+/// Returns the most likely encoding of `bytes`.
+pub fn detect(bytes: &[u8]) -> &'static Charset {
+    if let Some((charset, _)) = Charset::for_bom(bytes) {
+        return charset;
+    }
+
+    let sample = &bytes[..bytes.len().min(SAMPLE)];
+    if let Some(charset) = detect_utf16(sample) {
+        return charset;
+    }
+
+    // The sample may end in the middle of a character.
+    match std::str::from_utf8(sample) {
+        Ok(_) => return UTF_8,
+        Err(e) if e.error_len().is_none() => return UTF_8,
+        Err(_) => {}
+    }
+
+    // Accented letters in Western text are mostly surrounded by ASCII, while
+    // the characters of CJK encodings take two bytes that are both non-ASCII.
+    let high = sample.iter().filter(|b| !b.is_ascii()).count();
+    let paired = sample
+        .windows(2)
+        .filter(|pair| !pair[0].is_ascii() && !pair[1].is_ascii())
+        .count();
+    if paired * 2 < high {
+        return WINDOWS_1252;
+    }
+
+    let mut best = (WINDOWS_1252, 0);
+    for &charset in CANDIDATES {
+        let score = score(charset, sample);
+        if score > best.1 {
+            best = (charset, score);
+        }
+    }
+    best.0
+}
+
+/// Recognizes UTF-16 text without a byte order mark from the zero high bytes
+/// of its ASCII characters, which all fall on either odd or even offsets.
+fn detect_utf16(sample: &[u8]) -> Option<&'static Charset> {
+    let pairs = sample.len() / 2;
+    if pairs < 2 {
+        return None;
+    }
+
+    let (mut even, mut odd) = (0, 0);
+    for pair in sample.chunks_exact(2) {
+        even += usize::from(pair[0] == 0);
+        odd += usize::from(pair[1] == 0);
+    }
+
+    // Most characters of typical text are ASCII, so require a clear majority
+    // of zeros on one side and almost none on the other.
+    match (even * 10 / pairs, odd * 10 / pairs) {
+        (e, o) if e >= 4 && o == 0 => Some(UTF_16BE),
+        (e, o) if o >= 4 && e == 0 => Some(UTF_16LE),
+        _ => None,
+    }
+}
+
+/// Scores how plausible the text decoded from `sample` with `charset` is,
+/// from the characters most frequent in the language it is used for.
+/// Malformed sequences and characters rarely found in text, such as C1
+/// controls and half-width katakana, are heavily penalized.
+fn score(charset: &'static Charset, sample: &[u8]) -> i64 {
+    let (text, _) = charset.decode_without_bom_handling(sample);
+    let frequent = frequent(charset);
+    let mut score = 0;
+    for c in text.chars() {
+        score += match c {
+            '\u{fffd}' => -100,
+            '\u{80}'..='\u{9f}' | '\u{e000}'..='\u{f8ff}' => -20,
+            '\u{ff61}'..='\u{ff9f}' => -5,
+            c if frequent.contains(c) => 3,
+            _ => 0,
+        };
+    }
+    score
+}
+
+/// Returns the most frequent characters of the language of `charset`.
+fn frequent(charset: &'static Charset) -> &'static str {
+    if charset == SHIFT_JIS || charset == EUC_JP {
+        // Japanese is told apart by its kana; these are the most common.
+        "のにはをたがでてとしれさいるかなっもうあこま日人年ンスルトイ"
+    } else if charset == GBK {
+        "的一是不了人我在有他这中大来上国个到说们为子和你地出道也时年"
+    } else if charset == BIG5 {
+        "的一是不了人我在有他這中大來上國個到說們為子和你地出道也時年"
+    } else if charset == EUC_KR {
+        "이다는의에가을고하지서한기로사리자일어대시아수나도그"
+    } else {
+        ""
+    }
+}
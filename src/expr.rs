@@ -0,0 +1,275 @@
+//! Boolean expressions over patterns.
+//!
+//! An expression combines patterns with `AND`, `OR` and `NOT`, grouped with
+//! parentheses, as in `foo AND (bar OR baz) NOT qux`. Patterns written next
+//! to each other are joined with `AND`, which binds tighter than `OR`. A
+//! pattern containing spaces or parentheses, or spelled like an operator, is
+//! quoted with `'` or `"`, and `\` makes the quote that follows part of it.
+
+/// A node of the expression tree.
+#[derive(Clone)]
+enum Node {
+    /// Whether the pattern with this index matches.
+    Pattern(usize),
+    Not(Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+}
+
+/// A parsed expression along with the patterns it refers to.
+#[derive(Clone)]
+pub struct Expr {
+    root: Node,
+    patterns: Vec<String>,
+}
+
+#[derive(PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Pattern(String),
+}
+
+impl Expr {
+    /// Parses an expression.
+    pub fn parse(s: &str) -> Result<Expr, String> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+            patterns: Vec::new(),
+        };
+        let root = parser.or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err("unexpected ')' in expression".to_string());
+        }
+        Ok(Expr {
+            root,
+            patterns: parser.patterns,
+        })
+    }
+
+    /// Returns the expression requiring every one of `patterns` to match.
+    pub fn all(patterns: Vec<String>) -> Expr {
+        let root = (1..patterns.len()).fold(Node::Pattern(0), |node, i| {
+            Node::And(Box::new(node), Box::new(Node::Pattern(i)))
+        });
+        Expr { root, patterns }
+    }
+
+    /// Returns the patterns of the expression, in order of appearance.
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    /// Evaluates the expression, given whether the pattern with each index
+    /// matches.
+    pub fn eval(&self, matches: impl Fn(usize) -> bool) -> bool {
+        eval(&self.root, &matches)
+    }
+}
+
+fn eval(node: &Node, matches: &impl Fn(usize) -> bool) -> bool {
+    match node {
+        Node::Pattern(i) => matches(*i),
+        Node::Not(node) => !eval(node, matches),
+        Node::And(a, b) => eval(a, matches) && eval(b, matches),
+        Node::Or(a, b) => eval(a, matches) || eval(b, matches),
+    }
+}
+
+/// Splits `s` into parentheses, operators and patterns.
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '\'' | '"' => {
+                let mut pattern = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') if chars.peek() == Some(&c) => pattern.extend(chars.next()),
+                        Some(next) if next == c => break,
+                        Some(next) => pattern.push(next),
+                        None => return Err(format!("unclosed {c} in expression")),
+                    }
+                }
+                tokens.push(Token::Pattern(pattern));
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || next == '(' || next == ')' {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+                tokens.push(match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Pattern(word),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// A recursive descent parser, with one method per level of precedence.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    patterns: Vec<String>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    /// Parses operands joined by `OR`.
+    fn or(&mut self) -> Result<Node, String> {
+        let mut node = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            node = Node::Or(Box::new(node), Box::new(self.and()?));
+        }
+        Ok(node)
+    }
+
+    /// Parses operands joined by `AND`, or simply written one after another.
+    fn and(&mut self) -> Result<Node, String> {
+        let mut node = self.not()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.pos += 1,
+                Some(Token::Open | Token::Not | Token::Pattern(_)) => {}
+                _ => return Ok(node),
+            }
+            node = Node::And(Box::new(node), Box::new(self.not()?));
+        }
+    }
+
+    /// Parses an operand, negated by any number of `NOT`.
+    fn not(&mut self) -> Result<Node, String> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Not) => {
+                self.pos += 1;
+                Ok(Node::Not(Box::new(self.not()?)))
+            }
+            Some(Token::Open) => {
+                self.pos += 1;
+                let node = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err("missing ')' in expression".to_string());
+                }
+                self.pos += 1;
+                Ok(node)
+            }
+            Some(Token::Pattern(pattern)) => {
+                self.patterns.push(pattern.clone());
+                self.pos += 1;
+                Ok(Node::Pattern(self.patterns.len() - 1))
+            }
+            Some(Token::Close) => Err("unexpected ')' in expression".to_string()),
+            Some(Token::And | Token::Or) => {
+                Err("missing pattern before an operator in expression".to_string())
+            }
+            None => Err("missing pattern at the end of the expression".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns whether `expr` holds when only the patterns in `matching`
+    /// match.
+    fn holds(expr: &str, matching: &[&str]) -> bool {
+        let expr = Expr::parse(expr).unwrap();
+        expr.eval(|i| matching.contains(&expr.patterns()[i].as_str()))
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert!(holds("a OR b AND c", &["a"]));
+        assert!(!holds("a OR b AND c", &["b"]));
+        assert!(holds("a OR b AND c", &["b", "c"]));
+        assert!(!holds("a AND b OR c", &["a"]));
+        assert!(holds("a AND b OR c", &["c"]));
+    }
+
+    #[test]
+    fn adjacent_patterns_are_joined_with_and() {
+        assert!(holds("a b", &["a", "b"]));
+        assert!(!holds("a b", &["a"]));
+        assert!(!holds("a b OR c", &["b"]));
+        assert!(holds("a b OR c", &["c"]));
+    }
+
+    #[test]
+    fn not() {
+        assert!(holds("NOT a", &[]));
+        assert!(!holds("NOT a", &["a"]));
+        assert!(holds("NOT NOT a", &["a"]));
+        assert!(holds("a NOT b", &["a"]));
+        assert!(!holds("a NOT b", &["a", "b"]));
+        assert!(holds("NOT a OR b", &["a", "b"]));
+        assert!(!holds("NOT (a OR b)", &["b"]));
+    }
+
+    #[test]
+    fn parentheses() {
+        assert!(!holds("(a OR b) c", &["a"]));
+        assert!(holds("(a OR b) c", &["a", "c"]));
+        assert!(holds("a (b OR (c d))", &["a", "c", "d"]));
+        assert!(!holds("a (b OR (c d))", &["a", "d"]));
+    }
+
+    #[test]
+    fn patterns_in_order_of_appearance() {
+        let expr = Expr::parse("b OR a (c NOT b)").unwrap();
+        assert_eq!(expr.patterns(), ["b", "a", "c", "b"]);
+    }
+
+    #[test]
+    fn quoting() {
+        let expr = Expr::parse(r#"'foo bar' OR "AND" OR '(x)' OR 'it\'s' OR a\b"#).unwrap();
+        assert_eq!(expr.patterns(), ["foo bar", "AND", "(x)", "it's", r"a\b"]);
+        assert!(holds(r#""AND" "OR""#, &["AND", "OR"]));
+    }
+
+    #[test]
+    fn all() {
+        let expr = Expr::all(vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+        assert!(expr.eval(|_| true));
+        assert!(!expr.eval(|i| i != 1));
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        for expr in [
+            "",
+            "a AND",
+            "a OR",
+            "AND a",
+            "a OR OR b",
+            "NOT",
+            "(a",
+            "a)",
+            "()",
+            "'a",
+            "\"a",
+        ] {
+            assert!(Expr::parse(expr).is_err(), "{expr}");
+        }
+    }
+}
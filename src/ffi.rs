@@ -0,0 +1,141 @@
+//! C API for embedding the search in programs written in other languages.
+//!
+//! A pattern is compiled once with `grep_pattern_new` and then used to search
+//! any number of buffers or files. Each matching line is passed to a
+//! callback as it is found, which returns whether to keep searching. The
+//! declarations are in `include/grep.h`, and the shared library is built by
+//! `cargo rustc --lib --features ffi --crate-type cdylib`.
+
+use crate::lines::LineReader;
+use regex::bytes::Regex;
+use std::ffi::{c_char, c_void, CStr};
+use std::fs::File;
+use std::io::Read;
+use std::ptr;
+
+/// A compiled pattern, opaque to C.
+pub struct GrepPattern {
+    re: Regex,
+}
+
+/// Receives a matching line: its 1-based number, a pointer to its bytes
+/// without the line terminator and their length, and the `user_data` given
+/// to the search. The line is only valid during the call. Returns whether to
+/// keep searching.
+pub type GrepMatchCallback =
+    extern "C" fn(line_number: u64, line: *const u8, len: usize, user_data: *mut c_void) -> bool;
+
+/// Compiles the NUL-terminated UTF-8 `pattern`. Returns null when it is not
+/// valid UTF-8 or not a valid regular expression.
+///
+/// # Safety
+///
+/// `pattern` must point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn grep_pattern_new(pattern: *const c_char) -> *mut GrepPattern {
+    if pattern.is_null() {
+        return ptr::null_mut();
+    }
+    let Ok(pattern) = CStr::from_ptr(pattern).to_str() else {
+        return ptr::null_mut();
+    };
+    match Regex::new(pattern) {
+        Ok(re) => Box::into_raw(Box::new(GrepPattern { re })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Frees a pattern returned by `grep_pattern_new`. Null is ignored.
+///
+/// # Safety
+///
+/// `pattern` must be null or returned by `grep_pattern_new`, and not freed
+/// already.
+#[no_mangle]
+pub unsafe extern "C" fn grep_pattern_free(pattern: *mut GrepPattern) {
+    if !pattern.is_null() {
+        drop(Box::from_raw(pattern));
+    }
+}
+
+/// Searches the `len` bytes at `buf` for lines matching `pattern`, passing
+/// each to `callback` along with `user_data`. Returns the number of matching
+/// lines passed, or -1 when `pattern` is null.
+///
+/// # Safety
+///
+/// `pattern` must be returned by `grep_pattern_new`, and `buf` must point to
+/// `len` readable bytes unless `len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn grep_search_buffer(
+    pattern: *const GrepPattern,
+    buf: *const u8,
+    len: usize,
+    callback: GrepMatchCallback,
+    user_data: *mut c_void,
+) -> i64 {
+    let Some(pattern) = pattern.as_ref() else {
+        return -1;
+    };
+    let buf = if len == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(buf, len)
+    };
+    search(&pattern.re, buf, callback, user_data)
+}
+
+/// Searches the file at the NUL-terminated `path` like `grep_search_buffer`.
+/// Returns -1 when `pattern` is null or the file cannot be read in full, in
+/// which case the lines read up to the error may have been passed.
+///
+/// # Safety
+///
+/// `pattern` must be returned by `grep_pattern_new`, and `path` must point
+/// to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn grep_search_path(
+    pattern: *const GrepPattern,
+    path: *const c_char,
+    callback: GrepMatchCallback,
+    user_data: *mut c_void,
+) -> i64 {
+    let Some(pattern) = pattern.as_ref() else {
+        return -1;
+    };
+    if path.is_null() {
+        return -1;
+    }
+    let Ok(path) = CStr::from_ptr(path).to_str() else {
+        return -1;
+    };
+    match File::open(path) {
+        Ok(f) => search(&pattern.re, f, callback, user_data),
+        Err(_) => -1,
+    }
+}
+
+/// Passes the lines read from `reader` matching `re` to `callback` until it
+/// returns false, and returns how many were passed, or -1 when reading
+/// fails.
+fn search(
+    re: &Regex,
+    reader: impl Read,
+    callback: GrepMatchCallback,
+    user_data: *mut c_void,
+) -> i64 {
+    let mut lines = LineReader::new(reader, 0, 1, usize::MAX, usize::MAX);
+    let mut found = 0;
+    while let Some((number, _, line)) = lines.next_line() {
+        if re.is_match(line) {
+            found += 1;
+            if !callback(number as u64, line.as_ptr(), line.len(), user_data) {
+                break;
+            }
+        }
+    }
+    match lines.take_error() {
+        Some(_) => -1,
+        None => found,
+    }
+}
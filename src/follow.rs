@@ -0,0 +1,127 @@
+//! Following files as they grow, like `tail -f`, for --follow-lines.
+//!
+//! Each file is polled for the bytes appended since it was last read, which
+//! are split into lines once their newline arrives. A file that shrinks was
+//! truncated, and is read again from its start. A path that names another
+//! file than the one held open was rotated, and the new file is read from its
+//! start.
+
+use crate::stream;
+use memchr::{memchr, memrchr};
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::mem;
+
+/// A file being followed.
+pub struct Follower {
+    path: String,
+    file: File,
+    /// Offset of the first byte not read yet.
+    offset: usize,
+    /// Number of lines read so far.
+    number: usize,
+    /// The start of a line still waiting for its newline.
+    partial: Vec<u8>,
+}
+
+impl Follower {
+    /// Starts following the file at `path` from `offset`, just past line
+    /// `number`, where its search stopped.
+    pub fn new(path: &str, number: usize, offset: usize) -> io::Result<Follower> {
+        let mut follower = Follower {
+            path: path.to_string(),
+            file: File::open(path)?,
+            offset,
+            number,
+            partial: Vec::new(),
+        };
+        // A file truncated since is read again from its start once polled.
+        if follower.file.metadata()?.len() < offset as u64 {
+            return Ok(follower);
+        }
+        // A last line still waiting for its newline is read back, in blocks
+        // up to the newline before it.
+        let mut start = offset;
+        while start > 0 {
+            let mut block = vec![0; start.min(64 * 1024)];
+            start -= block.len();
+            follower.file.seek(SeekFrom::Start(start as u64))?;
+            follower.file.read_exact(&mut block)?;
+            let newline = memrchr(b'\n', &block);
+            if let Some(i) = newline {
+                block.drain(..=i);
+            }
+            block.append(&mut follower.partial);
+            follower.partial = block;
+            if newline.is_some() {
+                break;
+            }
+        }
+        if !follower.partial.is_empty() {
+            follower.number -= 1;
+        }
+        Ok(follower)
+    }
+
+    /// Returns the path of the file.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the lines completed since the last call, each along with its
+    /// number and offset, without their terminators.
+    pub fn poll(&mut self) -> io::Result<Vec<(usize, usize, String)>> {
+        let metadata = self.file.metadata()?;
+        match fs::metadata(&self.path) {
+            Ok(current) if !same_file(&metadata, &current) => {
+                self.file = File::open(&self.path)?;
+                self.restart();
+            }
+            // A file removed without being replaced yet is still open, and
+            // may still grow.
+            _ if (metadata.len() as usize) < self.offset => self.restart(),
+            _ => {}
+        }
+
+        self.file.seek(SeekFrom::Start(self.offset as u64))?;
+        let mut appended = Vec::new();
+        self.file.read_to_end(&mut appended)?;
+        self.offset += appended.len();
+
+        let mut lines = Vec::new();
+        let mut bytes = &appended[..];
+        while let Some(i) = memchr(b'\n', bytes) {
+            self.partial.extend_from_slice(&bytes[..i]);
+            bytes = &bytes[i + 1..];
+            let line = mem::take(&mut self.partial);
+            let offset = self.offset - bytes.len() - line.len() - 1;
+            let line = line.strip_suffix(b"\r").map(<[u8]>::to_vec).unwrap_or(line);
+            self.number += 1;
+            lines.push((self.number, offset, stream::to_string(line)));
+        }
+        self.partial.extend_from_slice(bytes);
+        Ok(lines)
+    }
+
+    /// Reads the file again from its start.
+    fn restart(&mut self) {
+        self.offset = 0;
+        self.number = 0;
+        self.partial.clear();
+    }
+}
+
+/// Returns whether `a` and `b` are the metadata of the same file.
+#[cfg(unix)]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+/// Returns whether `a` and `b` are the metadata of the same file, which
+/// without inode numbers is assumed.
+#[cfg(not(unix))]
+fn same_file(_a: &Metadata, _b: &Metadata) -> bool {
+    true
+}
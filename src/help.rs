@@ -0,0 +1,241 @@
+//! Extended help printed by `--help`.
+
+/// Section of the options controlling what matches.
+pub const MATCHING: &str = "Matching";
+/// Section of the options controlling what is printed.
+pub const OUTPUT: &str = "Output";
+/// Section of the options restricting which files and parts of files are
+/// searched.
+pub const FILTERING: &str = "Filtering";
+/// Section of the options tuning how files are read and searched.
+pub const PERFORMANCE: &str = "Performance";
+
+/// Description printed by `--help` above the options.
+pub const LONG_ABOUT: &str = "\
+Searches files for lines matching a regular expression.
+
+Every FILE given with --file is searched for PATTERN, and the matching lines
+are printed, prefixed with the name of the file when several files are
+searched. The pattern uses the syntax of the regex
+crate, documented at https://docs.rs/regex/latest/regex/#syntax.
+
+Searching is the default command. The others list the files that would be
+searched, replace matches, and list the document types that can be decoded,
+each with its own options shown by `grep help COMMAND`.";
+
+/// Examples printed by `--help`, as pairs of a description and a command.
+const EXAMPLES: &[(&str, &str)] = &[
+    ("Search a file for a word", "grep -f notes.txt -e todo"),
+    (
+        "Search several files, printing line numbers and two lines of context",
+        "grep -f a.txt -f b.txt -e 'fn main' -n -C 2",
+    ),
+    (
+        "Show the whole function around each match",
+        "grep -f src/main.rs -e unwrap -W",
+    ),
+    (
+        "Print the function containing each match above it",
+        "grep -f src/lib.rs -e todo! --show-heading",
+    ),
+    (
+        "Print the log entries whose stack trace mentions a function",
+        "grep -f app.log -e parse_config --record-separator '^\\d{4}-\\d{2}-\\d{2}'",
+    ),
+    (
+        "Print the changelog entries mentioning a fix",
+        "grep -f CHANGELOG.md -i -e fix --paragraph",
+    ),
+    (
+        "Search the messages of a structured log, printing the path of each",
+        "grep -f events.ndjson -e timeout --json-input",
+    ),
+    (
+        "Search only the email column of a CSV file",
+        "grep -f users.csv -e '@example\\.com$' --csv --columns email",
+    ),
+    (
+        "Find a byte sequence with a wildcard in a firmware image",
+        "grep -f firmware.bin --hex 'DE AD ?? EF' -C 1",
+    ),
+    (
+        "Search for a pattern starting with a dash",
+        "grep -f log.txt -e -verbose",
+    ),
+    (
+        "List the files containing a match",
+        "grep -f a.txt -f b.txt -e error -l",
+    ),
+    (
+        "Count the lines logging errors in each file, even when there are none",
+        "grep -f a.rs -f b.rs -e 'error!' -c --include-zero",
+    ),
+    (
+        "Find the files logging an error the most",
+        "grep -r -f logs -e 'connection refused' -l --count-summary",
+    ),
+    (
+        "Show the first place a function is called, in any file",
+        "grep -r -f src -e 'parse_config\\(' -1 -j 8",
+    ),
+    (
+        "List the files containing both of two words",
+        "grep -r -f src -e unsafe -e unwrap --all-match -l",
+    ),
+    (
+        "Search for lines combining patterns with boolean logic",
+        "grep -f app.log --expr 'error AND (db OR cache) NOT retry'",
+    ),
+    (
+        "Print only the first capture group of each match",
+        "grep -f access.log -e 'GET (\\S+)' -o --group 1",
+    ),
+    (
+        "List the distinct email addresses in a directory",
+        "grep -r -f mail -e '[\\w.+-]+@[\\w-]+\\.[\\w.]+' -o --unique",
+    ),
+    (
+        "Count the responses with each status code",
+        "grep -f access.log -e '\" (?<status>\\d{3}) ' --histogram status",
+    ),
+    (
+        "Summarize a log by error code, with the first and last occurrence of each",
+        "grep -f app.log -e 'error (?<code>E\\d+)' --group-by code --group-lines",
+    ),
+    (
+        "Swap two words in the matching lines",
+        "grep replace '$2 $1' -f names.txt -e '(\\w+) (\\w+)'",
+    ),
+    (
+        "Review a rename across a tree as a patch before making it",
+        "grep replace 'new_name' -r -f src -e '\\bold_name\\b' --diff > rename.patch",
+    ),
+    (
+        "Make the rename in place, keeping the originals in a directory",
+        "grep replace 'new_name' -r -f src -e '\\bold_name\\b' --write --backup-dir /tmp/orig",
+    ),
+    (
+        "Search the first megabyte of a large file in parallel",
+        "grep -f big.log -e timeout --byte-range ..1048575 -j 4",
+    ),
+    (
+        "Search a build tree in CI without spending more than a minute on it",
+        "grep -r -f target -e 'warning:' --timeout 10s --global-timeout 1m",
+    ),
+    (
+        "Watch a log for new errors, like tail -f piped to grep",
+        "grep -f app.log -e ERROR --follow-lines",
+    ),
+    (
+        "Search files modified during the last day",
+        "grep -f a.log -f b.log -e panic --newer-than 1d",
+    ),
+    (
+        "Search the errors logged during the last two hours",
+        "grep -f app.log -e ERROR --since 2h",
+    ),
+    (
+        "Find the test files below a directory by their path",
+        "grep -f src -e '_test\\.rs$' --path-match",
+    ),
+    (
+        "Scan a large tree so that the scan can be resumed if interrupted",
+        "grep -r -f /data -e secret --checkpoint scan.ckpt --resume scan.ckpt",
+    ),
+    (
+        "Search a log served over HTTPS",
+        "grep -f https://example.com/logs/app.log -e ERROR",
+    ),
+    (
+        "Search the logs archived in an S3 bucket for a request ID",
+        "grep -f s3://logs/2024/05/ -e 'req-8f3a2c'",
+    ),
+    (
+        "Pick a match on the terminal and open it in an editor",
+        "loc=$(grep -r -f src -e TODO --select) && $EDITOR +${loc##*:} ${loc%:*}",
+    ),
+    (
+        "List the files below a directory, without searching them",
+        "grep files -f src",
+    ),
+    (
+        "Print the results as JSON Lines for another program",
+        "grep -f data.csv -e '^\\d+,' --json",
+    ),
+];
+
+/// Formats the examples printed by `--help` below the options.
+pub fn examples() -> String {
+    let mut help = String::from("Examples:\n");
+    for (description, command) in EXAMPLES {
+        help.push_str(&format!("  {description}:\n      {command}\n\n"));
+    }
+    help.truncate(help.trim_end().len());
+    help
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EXAMPLES;
+    use crate::{Cli, Command};
+    use clap::Parser;
+
+    /// Splits the grep command of an example into its arguments as the
+    /// shell would, leaving out a redirection and whatever surrounds a
+    /// command substitution.
+    fn arguments(command: &str) -> Vec<String> {
+        let command = match command.split_once("$(") {
+            Some((_, rest)) => rest.split_once(')').map_or(rest, |(inner, _)| inner),
+            None => command,
+        };
+        let command = command.split(" > ").next().unwrap_or(command);
+
+        let mut args = Vec::new();
+        let mut arg = String::new();
+        let mut quote = None;
+        let mut chars = command.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some('"'), '\\') if chars.peek() == Some(&'"') => arg.extend(chars.next()),
+                (Some(_), c) => arg.push(c),
+                (None, '\'' | '"') => quote = Some(c),
+                (None, ' ') => {
+                    if !arg.is_empty() {
+                        args.push(std::mem::take(&mut arg));
+                    }
+                }
+                (None, c) => arg.push(c),
+            }
+        }
+        args.extend((!arg.is_empty()).then_some(arg));
+        args
+    }
+
+    #[test]
+    fn examples_parse() {
+        for (description, command) in EXAMPLES {
+            let args = arguments(command);
+            assert_eq!(args[0], "grep", "{description}");
+            let cli = match Cli::try_parse_from(&args) {
+                Ok(cli) => cli,
+                Err(e) => panic!("{description}: {command}\n{e}"),
+            };
+            match cli.into_search() {
+                // An option taken as the pattern, as in `-e -i`, is a
+                // mistake.
+                Ok(search) => {
+                    for pattern in &search.pattern {
+                        let is_option = pattern.len() == 2 && pattern.starts_with('-');
+                        assert!(!is_option, "{description}: {command}");
+                    }
+                    let has_pattern =
+                        !search.pattern.is_empty() || search.expr.is_some() || search.hex.is_some();
+                    assert!(has_pattern, "{description}: {command}");
+                }
+                Err(Command::Files(_)) => {}
+                Err(_) => panic!("{description}: {command}"),
+            }
+        }
+    }
+}
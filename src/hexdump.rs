@@ -0,0 +1,132 @@
+//! Hex dump output for matches in binary files.
+//!
+//! Every match is shown within the rows of 16 bytes that contain it, laid out
+//! like `hexdump -C`: the offset of the row, the bytes in hex and the bytes
+//! as ASCII, with non-printable bytes shown as dots.
+//!
+//! Bytes can also be searched for with a hex pattern such as `DE AD ?? EF`,
+//! where `??` stands for any byte.
+
+use grep::interval::{Interval, IntervalSet};
+use std::io::{self, Write};
+use std::ops::Range;
+
+const ROW: usize = 16;
+const MATCH_START: &str = "\x1b[1;31m";
+const MATCH_END: &str = "\x1b[0m";
+
+/// Prints the rows of `buf` containing `matches`, along with `before_rows`
+/// and `after_rows` rows of context. Offsets are printed relative to `base`,
+/// the offset of `buf` within its file. Rows that are not adjacent are
+/// separated by `--`, and the matched bytes are colored when `color` is set.
+pub fn print(
+    out: &mut impl Write,
+    buf: &[u8],
+    base: usize,
+    matches: &[Range<usize>],
+    before_rows: usize,
+    after_rows: usize,
+    color: bool,
+) -> io::Result<()> {
+    let Some(last_byte) = buf.len().checked_sub(1) else {
+        return Ok(());
+    };
+    let all_rows = Interval::new(0, last_byte / ROW);
+
+    // The rows to print, as coalesced intervals of row indices. An empty
+    // match at the very end of the buffer has no row of its own.
+    let rows: IntervalSet<usize> = matches
+        .iter()
+        .filter_map(|m| {
+            let last = (m.end.max(m.start + 1) - 1) / ROW;
+            Interval::new(m.start / ROW, last)
+                .expand(before_rows, after_rows, None)
+                .intersect(&all_rows)
+        })
+        .collect();
+
+    let mut matches = matches.iter().peekable();
+    let mut previous: Option<&Interval<usize>> = None;
+    for interval in &rows {
+        // Adjacent intervals continue one another without a separator.
+        if previous.is_some_and(|previous| previous.gap(interval).is_some()) {
+            writeln!(out, "--")?;
+        }
+        previous = Some(interval);
+
+        for row in interval.iter() {
+            let start = row * ROW;
+            let bytes = &buf[start..buf.len().min(start + ROW)];
+
+            // Matches entirely before this row are no longer needed.
+            while matches
+                .next_if(|m| m.end <= start && m.start < start)
+                .is_some()
+            {}
+            let is_match = |offset: usize| {
+                color
+                    && matches
+                        .clone()
+                        .take_while(|m| m.start <= offset)
+                        .any(|m| m.contains(&offset))
+            };
+
+            let mut hex = String::new();
+            let mut ascii = String::new();
+            for (j, &byte) in bytes.iter().enumerate() {
+                if j == ROW / 2 {
+                    hex.push(' ');
+                }
+                let c = match byte {
+                    0x20..=0x7e => byte as char,
+                    _ => '.',
+                };
+                if is_match(start + j) {
+                    hex.push_str(&format!(" {MATCH_START}{byte:02x}{MATCH_END}"));
+                    ascii.push_str(&format!("{MATCH_START}{c}{MATCH_END}"));
+                } else {
+                    hex.push_str(&format!(" {byte:02x}"));
+                    ascii.push(c);
+                }
+            }
+
+            // Pad short rows so the ASCII column stays aligned.
+            let missing = ROW - bytes.len();
+            let padding = 3 * missing + usize::from(bytes.len() <= ROW / 2);
+            writeln!(
+                out,
+                "{:08x} {hex}{}  |{ascii}|",
+                base + start,
+                " ".repeat(padding)
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses a hex byte pattern such as `DE AD ?? EF`, in which `??` stands for
+/// any byte and spaces are optional, into the regex matching those bytes
+/// once compiled without Unicode support.
+pub fn parse_pattern(pattern: &str) -> Result<String, String> {
+    let digits: Vec<char> = pattern.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.is_empty() {
+        return Err("expected at least one byte".to_string());
+    }
+    if !digits.len().is_multiple_of(2) {
+        return Err("expected two hex digits per byte".to_string());
+    }
+
+    let mut re = String::new();
+    for pair in digits.chunks(2) {
+        match pair {
+            ['?', '?'] => re.push_str("(?s:.)"),
+            [high, low] if high.is_ascii_hexdigit() && low.is_ascii_hexdigit() => {
+                re.push_str(&format!(r"\x{high}{low}"));
+            }
+            [high, low] => return Err(format!("invalid byte '{high}{low}'")),
+            _ => unreachable!("bytes are made of two digits"),
+        }
+    }
+    Ok(re)
+}
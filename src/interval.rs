@@ -0,0 +1,753 @@
+//! Closed intervals, used to track the lines selected for printing.
+//!
+//! With the `serde` feature, intervals serialize as `{"start":S,"end":E}`
+//! and interval sets as arrays of intervals.
+
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::ops::{Add, Range, RangeInclusive, Sub};
+use std::str::FromStr;
+
+/// A closed interval covering every value from `start` to `end`, inclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(
+        try_from = "Bounds<T>",
+        bound(deserialize = "T: serde::Deserialize<'de> + PartialOrd")
+    )
+)]
+pub struct Interval<T> {
+    pub start: T,
+    pub end: T,
+}
+
+/// The bounds of an interval as they are deserialized, before they are
+/// checked to be in order.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct Bounds<T> {
+    start: T,
+    end: T,
+}
+
+#[cfg(feature = "serde")]
+impl<T: PartialOrd> TryFrom<Bounds<T>> for Interval<T> {
+    type Error = &'static str;
+
+    fn try_from(bounds: Bounds<T>) -> Result<Interval<T>, &'static str> {
+        if bounds.start > bounds.end {
+            return Err("interval ends before it starts");
+        }
+        Ok(Interval {
+            start: bounds.start,
+            end: bounds.end,
+        })
+    }
+}
+
+impl<T: PartialOrd> Interval<T> {
+    /// Creates the interval from `start` to `end`, which must not be before
+    /// `start`.
+    pub fn new(start: T, end: T) -> Interval<T> {
+        debug_assert!(start <= end, "interval ends before it starts");
+        Interval { start, end }
+    }
+
+    /// Returns whether `value` lies within the interval.
+    pub fn contains(&self, value: T) -> bool {
+        self.start <= value && value <= self.end
+    }
+
+    /// Returns whether every value of `other` lies within the interval.
+    pub fn contains_interval(&self, other: &Interval<T>) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    /// Returns whether the interval and `other` have any value in common.
+    pub fn overlaps(&self, other: &Interval<T>) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+}
+
+impl<T: Copy + PartialOrd> Interval<T> {
+    /// Returns the values common to the interval and `other`, if any.
+    pub fn intersect(&self, other: &Interval<T>) -> Option<Interval<T>> {
+        let start = if self.start < other.start {
+            other.start
+        } else {
+            self.start
+        };
+        let end = if self.end > other.end {
+            other.end
+        } else {
+            self.end
+        };
+        (start <= end).then_some(Interval { start, end })
+    }
+
+    /// Returns the smallest interval covering both the interval and `other`,
+    /// which is their union when they overlap.
+    pub fn merge(&self, other: &Interval<T>) -> Interval<T> {
+        Interval {
+            start: if other.start < self.start {
+                other.start
+            } else {
+                self.start
+            },
+            end: if other.end > self.end {
+                other.end
+            } else {
+                self.end
+            },
+        }
+    }
+}
+
+// A closed interval always covers at least one value, so it is never empty.
+#[allow(clippy::len_without_is_empty)]
+impl<T: Copy + Add<Output = T> + Sub<Output = T> + From<u8>> Interval<T> {
+    /// Returns the number of values covered by the interval.
+    pub fn len(&self) -> T {
+        self.end - self.start + T::from(1)
+    }
+
+    /// Returns whether `other` starts right after the interval ends, or ends
+    /// right before it starts, so that the two cover consecutive values
+    /// without overlapping.
+    pub fn is_adjacent(&self, other: &Interval<T>) -> bool
+    where
+        T: PartialOrd,
+    {
+        // Subtracting the smaller bound cannot overflow, unlike adding one.
+        let one = T::from(1);
+        (other.start > self.end && other.start - self.end == one)
+            || (self.start > other.end && self.start - other.end == one)
+    }
+
+    /// Returns the interval of values strictly between the interval and
+    /// `other`, or `None` when they overlap or are adjacent.
+    pub fn gap(&self, other: &Interval<T>) -> Option<Interval<T>>
+    where
+        T: PartialOrd,
+    {
+        let one = T::from(1);
+        let (first, second) = if self.end < other.start {
+            (self, other)
+        } else if other.end < self.start {
+            (other, self)
+        } else {
+            return None;
+        };
+        (second.start - first.end > one).then(|| Interval {
+            start: first.end + one,
+            end: second.start - one,
+        })
+    }
+
+    /// Returns the number of values strictly between the interval and
+    /// `other`, which is zero when they overlap or are adjacent.
+    pub fn distance(&self, other: &Interval<T>) -> T
+    where
+        T: PartialOrd,
+    {
+        self.gap(other).map_or(T::from(0), |gap| gap.len())
+    }
+
+    /// Removes the values of `other` from the interval, returning what is
+    /// left before and after it.
+    pub fn subtract(&self, other: &Interval<T>) -> (Option<Interval<T>>, Option<Interval<T>>)
+    where
+        T: PartialOrd,
+    {
+        if !self.overlaps(other) {
+            return if other.start > self.end {
+                (Some(*self), None)
+            } else {
+                (None, Some(*self))
+            };
+        }
+
+        let one = T::from(1);
+        let before = (self.start < other.start).then(|| Interval {
+            start: self.start,
+            end: other.start - one,
+        });
+        let after = (other.end < self.end).then(|| Interval {
+            start: other.end + one,
+            end: self.end,
+        });
+        (before, after)
+    }
+
+    /// Splits the interval into the values before `point` and the values
+    /// from `point` on.
+    pub fn split_at(&self, point: T) -> (Option<Interval<T>>, Option<Interval<T>>)
+    where
+        T: PartialOrd,
+    {
+        if point <= self.start {
+            (None, Some(*self))
+        } else if point > self.end {
+            (Some(*self), None)
+        } else {
+            (
+                Some(Interval {
+                    start: self.start,
+                    end: point - T::from(1),
+                }),
+                Some(Interval {
+                    start: point,
+                    end: self.end,
+                }),
+            )
+        }
+    }
+}
+
+impl Interval<usize> {
+    /// Grows the interval by `before` values at its start and `after` values
+    /// at its end, without going below zero or above `max` when given. An
+    /// interval starting past `max` shrinks to `max` alone.
+    pub fn expand(&self, before: usize, after: usize, max: Option<usize>) -> Interval<usize> {
+        let start = self.start.saturating_sub(before);
+        let end = self.end.saturating_add(after);
+        match max {
+            Some(max) => Interval {
+                start: start.min(max),
+                end: end.min(max),
+            },
+            None => Interval { start, end },
+        }
+    }
+}
+
+impl<T: Copy> Interval<T>
+where
+    RangeInclusive<T>: Iterator<Item = T>,
+{
+    /// Returns an iterator over the values covered by the interval, in order.
+    pub fn iter(&self) -> RangeInclusive<T> {
+        RangeInclusive::from(*self)
+    }
+}
+
+impl<T> IntoIterator for Interval<T>
+where
+    RangeInclusive<T>: Iterator<Item = T>,
+{
+    type Item = T;
+    type IntoIter = RangeInclusive<T>;
+
+    fn into_iter(self) -> RangeInclusive<T> {
+        RangeInclusive::from(self)
+    }
+}
+
+/// An empty range, such as `5..=2` or one whose iterator is exhausted, has
+/// no equivalent interval.
+impl<T: PartialOrd> TryFrom<RangeInclusive<T>> for Interval<T> {
+    type Error = String;
+
+    fn try_from(range: RangeInclusive<T>) -> Result<Interval<T>, String> {
+        if range.is_empty() {
+            return Err("cannot convert an empty range to an interval".to_string());
+        }
+        let (start, end) = range.into_inner();
+        Ok(Interval { start, end })
+    }
+}
+
+impl<T> From<Interval<T>> for RangeInclusive<T> {
+    fn from(interval: Interval<T>) -> RangeInclusive<T> {
+        interval.start..=interval.end
+    }
+}
+
+/// A half-open range converts to the interval ending just before its end. An
+/// empty range has no equivalent interval.
+impl<T: Copy + PartialOrd + Sub<Output = T> + From<u8>> TryFrom<Range<T>> for Interval<T> {
+    type Error = String;
+
+    fn try_from(range: Range<T>) -> Result<Interval<T>, String> {
+        if range.start >= range.end {
+            return Err("cannot convert an empty range to an interval".to_string());
+        }
+        Ok(Interval {
+            start: range.start,
+            end: range.end - T::from(1),
+        })
+    }
+}
+
+/// An interval converts to the half-open range ending just after its end,
+/// which does not exist when the interval ends at the largest value.
+macro_rules! try_from_interval {
+    ($($t:ty),*) => {$(
+        impl TryFrom<Interval<$t>> for Range<$t> {
+            type Error = String;
+
+            fn try_from(interval: Interval<$t>) -> Result<Range<$t>, String> {
+                let end = interval.end.checked_add(1).ok_or_else(|| {
+                    format!("cannot convert an interval ending at {} to a range", interval.end)
+                })?;
+                Ok(interval.start..end)
+            }
+        }
+    )*};
+}
+
+try_from_interval!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+/// Parses an interval written as `START..END`, as `[START,END]` or as a
+/// single value covered on its own. Both bounds are inclusive.
+impl<T: Copy + FromStr + PartialOrd + Display> FromStr for Interval<T>
+where
+    T::Err: Display,
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Interval<T>, String> {
+        let (start, end) = if let Some(bounds) = s.strip_prefix('[') {
+            bounds
+                .strip_suffix(']')
+                .and_then(|bounds| bounds.split_once(','))
+                .ok_or_else(|| format!("expected [START,END], found '{s}'"))?
+        } else if let Some(bounds) = s.split_once("..") {
+            bounds
+        } else {
+            let value: T = s
+                .trim()
+                .parse()
+                .map_err(|e| format!("invalid value '{s}': {e}"))?;
+            return Ok(Interval {
+                start: value,
+                end: value,
+            });
+        };
+
+        let start: T = start
+            .trim()
+            .parse()
+            .map_err(|e| format!("invalid START: {e}"))?;
+        let end: T = end
+            .trim()
+            .parse()
+            .map_err(|e| format!("invalid END: {e}"))?;
+        if start > end {
+            return Err(format!("START ({start}) is greater than END ({end})"));
+        }
+
+        Ok(Interval { start, end })
+    }
+}
+
+/// Intervals are ordered when they are disjoint. Overlapping intervals are
+/// not comparable.
+impl<T: PartialOrd> PartialOrd for Interval<T> {
+    fn partial_cmp(&self, other: &Interval<T>) -> Option<Ordering> {
+        if self == other {
+            Some(Ordering::Equal)
+        } else if self.end < other.start {
+            Some(Ordering::Less)
+        } else if other.end < self.start {
+            Some(Ordering::Greater)
+        } else {
+            None
+        }
+    }
+}
+
+/// An interval ordered by its start and then by its end, so that intervals
+/// can be sorted or stored in a `BTreeSet` even when they overlap. For
+/// disjoint intervals the order agrees with that of `Interval`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ByStart<T>(pub Interval<T>);
+
+impl<T: Ord> Ord for ByStart<T> {
+    fn cmp(&self, other: &ByStart<T>) -> Ordering {
+        self.0
+            .start
+            .cmp(&other.0.start)
+            .then_with(|| self.0.end.cmp(&other.0.end))
+    }
+}
+
+impl<T: Ord> PartialOrd for ByStart<T> {
+    fn partial_cmp(&self, other: &ByStart<T>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> From<Interval<T>> for ByStart<T> {
+    fn from(interval: Interval<T>) -> ByStart<T> {
+        ByStart(interval)
+    }
+}
+
+/// A set of values stored as sorted, disjoint intervals. Intervals that
+/// overlap or are adjacent are coalesced as they are inserted.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IntervalSet<T> {
+    intervals: Vec<Interval<T>>,
+}
+
+impl<T: Copy + Ord + Add<Output = T> + Sub<Output = T> + From<u8>> IntervalSet<T> {
+    /// Creates an empty set.
+    pub fn new() -> IntervalSet<T> {
+        IntervalSet {
+            intervals: Vec::new(),
+        }
+    }
+
+    /// Returns whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// Adds the values of `interval` to the set, coalescing it with the
+    /// intervals it overlaps or is adjacent to.
+    pub fn insert(&mut self, mut interval: Interval<T>) {
+        // Intervals ending before the new one starts are left alone, as are
+        // those starting after it ends, unless they are adjacent to it.
+        // Everything in between is coalesced with it.
+        let first = self
+            .intervals
+            .partition_point(|i| i.end < interval.start && !i.is_adjacent(&interval));
+        let last = first
+            + self.intervals[first..]
+                .partition_point(|i| i.start <= interval.end || i.is_adjacent(&interval));
+        if first < last {
+            interval = interval
+                .merge(&self.intervals[first])
+                .merge(&self.intervals[last - 1]);
+        }
+        self.intervals.splice(first..last, [interval]);
+    }
+
+    /// Returns whether `value` is in the set.
+    pub fn contains(&self, value: T) -> bool {
+        let i = self.intervals.partition_point(|i| i.end < value);
+        self.intervals.get(i).is_some_and(|i| i.contains(value))
+    }
+
+    /// Returns an iterator over the intervals of the set, in order.
+    pub fn iter(&self) -> std::slice::Iter<'_, Interval<T>> {
+        self.intervals.iter()
+    }
+
+    /// Returns the set of values in either `self` or `other`.
+    pub fn union(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        let mut union = self.clone();
+        for &interval in other {
+            union.insert(interval);
+        }
+        union
+    }
+
+    /// Returns the set of values in both `self` and `other`.
+    pub fn intersection(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        let mut intersection = IntervalSet::new();
+        let (mut a, mut b) = (self.intervals.iter(), other.intervals.iter());
+        let (mut x, mut y) = (a.next(), b.next());
+        while let (Some(i), Some(j)) = (x, y) {
+            if let Some(common) = i.intersect(j) {
+                intersection.intervals.push(common);
+            }
+            // Whichever interval ends first cannot overlap anything further.
+            if i.end < j.end {
+                x = a.next();
+            } else {
+                y = b.next();
+            }
+        }
+        intersection
+    }
+}
+
+impl<T: Copy + Ord + Add<Output = T> + Sub<Output = T> + From<u8>> IntervalSet<T> {
+    /// Returns the set of values in `self` but not in `other`.
+    pub fn difference(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        let mut difference = IntervalSet::new();
+        for &interval in &self.intervals {
+            let mut rest = Some(interval);
+            for j in other.intervals.iter() {
+                let Some(i) = rest else {
+                    break;
+                };
+                if i.end < j.start {
+                    break;
+                }
+                let before;
+                (before, rest) = i.subtract(j);
+                difference.intervals.extend(before);
+            }
+            difference.intervals.extend(rest);
+        }
+        difference
+    }
+}
+
+impl<'a, T> IntoIterator for &'a IntervalSet<T> {
+    type Item = &'a Interval<T>;
+    type IntoIter = std::slice::Iter<'a, Interval<T>>;
+
+    fn into_iter(self) -> std::slice::Iter<'a, Interval<T>> {
+        self.intervals.iter()
+    }
+}
+
+impl<T: Copy + Ord + Add<Output = T> + Sub<Output = T> + From<u8>> FromIterator<Interval<T>>
+    for IntervalSet<T>
+{
+    fn from_iter<I: IntoIterator<Item = Interval<T>>>(iter: I) -> IntervalSet<T> {
+        let mut set = IntervalSet::new();
+        for interval in iter {
+            set.insert(interval);
+        }
+        set
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for IntervalSet<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.intervals.serialize(serializer)
+    }
+}
+
+/// The intervals are coalesced as they are read, so they need not be sorted
+/// or disjoint.
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for IntervalSet<T>
+where
+    T: Copy + Ord + Add<Output = T> + Sub<Output = T> + From<u8> + serde::Deserialize<'de>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let intervals = Vec::<Interval<T>>::deserialize(deserializer)?;
+        Ok(intervals.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contains() {
+        let interval = Interval::new(2, 4);
+        assert!(!interval.contains(1));
+        assert!(interval.contains(2));
+        assert!(interval.contains(4));
+        assert!(!interval.contains(5));
+    }
+
+    #[test]
+    fn overlaps() {
+        let interval = Interval::new(2, 4);
+        assert!(interval.overlaps(&Interval::new(4, 6)));
+        assert!(interval.overlaps(&Interval::new(0, 2)));
+        assert!(interval.overlaps(&Interval::new(3, 3)));
+        assert!(!interval.overlaps(&Interval::new(5, 6)));
+        assert!(!interval.overlaps(&Interval::new(0, 1)));
+    }
+
+    #[test]
+    fn intersect() {
+        let interval = Interval::new(2, 6);
+        assert_eq!(
+            interval.intersect(&Interval::new(4, 9)),
+            Some(Interval::new(4, 6))
+        );
+        assert_eq!(
+            interval.intersect(&Interval::new(3, 4)),
+            Some(Interval::new(3, 4))
+        );
+        assert_eq!(
+            interval.intersect(&Interval::new(6, 6)),
+            Some(Interval::new(6, 6))
+        );
+        assert_eq!(interval.intersect(&Interval::new(7, 9)), None);
+    }
+
+    #[test]
+    fn merge() {
+        let interval = Interval::new(2, 4);
+        assert_eq!(interval.merge(&Interval::new(3, 6)), Interval::new(2, 6));
+        assert_eq!(interval.merge(&Interval::new(5, 6)), Interval::new(2, 6));
+        assert_eq!(interval.merge(&Interval::new(3, 3)), Interval::new(2, 4));
+        assert_eq!(interval.merge(&Interval::new(8, 9)), Interval::new(2, 9));
+    }
+
+    #[test]
+    fn merge_takes_the_outer_bounds() {
+        let interval = Interval::new(4, 8);
+        // `other` starting first, ending inside, or covering the interval.
+        assert_eq!(interval.merge(&Interval::new(1, 5)), Interval::new(1, 8));
+        assert_eq!(interval.merge(&Interval::new(5, 6)), Interval::new(4, 8));
+        assert_eq!(interval.merge(&Interval::new(0, 9)), Interval::new(0, 9));
+        assert_eq!(interval.merge(&Interval::new(0, 1)), Interval::new(0, 8));
+        for other in [
+            Interval::new(1, 5),
+            Interval::new(5, 6),
+            Interval::new(9, 12),
+        ] {
+            assert_eq!(interval.merge(&other), other.merge(&interval));
+        }
+    }
+
+    #[test]
+    fn expand() {
+        let interval = Interval::new(5, 7);
+        assert_eq!(interval.expand(2, 3, None), Interval::new(3, 10));
+        assert_eq!(interval.expand(9, 0, None), Interval::new(0, 7));
+        assert_eq!(interval.expand(0, 9, Some(12)), Interval::new(5, 12));
+        assert_eq!(
+            Interval::new(1, usize::MAX).expand(0, 1, None),
+            Interval::new(1, usize::MAX)
+        );
+        assert_eq!(interval.expand(1, 1, Some(3)), Interval::new(3, 3));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_checks_bounds() {
+        use serde::de::value::{Error, MapDeserializer};
+        use serde::Deserialize;
+
+        let bounds = |start: u32, end: u32| {
+            MapDeserializer::<_, Error>::new([("start", start), ("end", end)].into_iter())
+        };
+        assert_eq!(Interval::deserialize(bounds(2, 5)), Ok(Interval::new(2, 5)));
+        assert!(Interval::<u32>::deserialize(bounds(5, 2)).is_err());
+    }
+
+    #[test]
+    fn insert_coalesces_overlapping_and_adjacent() {
+        let mut set = IntervalSet::new();
+        set.insert(Interval::new(1, 2));
+        set.insert(Interval::new(3, 4));
+        assert_eq!(set.iter().collect::<Vec<_>>(), [&Interval::new(1, 4)]);
+
+        set.insert(Interval::new(8, 9));
+        set.insert(Interval::new(6, 6));
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            [
+                &Interval::new(1, 4),
+                &Interval::new(6, 6),
+                &Interval::new(8, 9)
+            ]
+        );
+
+        set.insert(Interval::new(5, 7));
+        assert_eq!(set.iter().collect::<Vec<_>>(), [&Interval::new(1, 9)]);
+
+        set.insert(Interval::new(0, 0));
+        set.insert(Interval::new(2, 3));
+        assert_eq!(set.iter().collect::<Vec<_>>(), [&Interval::new(0, 9)]);
+    }
+
+    #[test]
+    fn insert_at_the_bounds() {
+        let mut set = IntervalSet::new();
+        set.insert(Interval::new(u8::MAX, u8::MAX));
+        set.insert(Interval::new(0, 0));
+        set.insert(Interval::new(1, 254));
+        assert_eq!(set.iter().collect::<Vec<_>>(), [&Interval::new(0, u8::MAX)]);
+    }
+
+    #[test]
+    fn set_contains() {
+        let set: IntervalSet<usize> = [Interval::new(1, 2), Interval::new(5, 6)]
+            .into_iter()
+            .collect();
+        assert!(!set.contains(0));
+        assert!(set.contains(2));
+        assert!(!set.contains(3));
+        assert!(set.contains(5));
+        assert!(!set.contains(7));
+    }
+
+    #[test]
+    fn overlaps_is_symmetric() {
+        let interval = Interval::new(4, 8);
+        for other in [
+            Interval::new(1, 4),
+            Interval::new(1, 9),
+            Interval::new(5, 6),
+            Interval::new(8, 12),
+            Interval::new(0, 3),
+            Interval::new(9, 12),
+        ] {
+            assert_eq!(interval.overlaps(&other), other.overlaps(&interval));
+        }
+        assert!(Interval::new(5, 6).overlaps(&interval));
+        assert!(!Interval::new(9, 12).overlaps(&interval));
+    }
+
+    /// Returns the set of the intervals given by their bounds.
+    fn set(intervals: &[(u32, u32)]) -> IntervalSet<u32> {
+        intervals
+            .iter()
+            .map(|&(start, end)| Interval::new(start, end))
+            .collect()
+    }
+
+    #[test]
+    fn union() {
+        let a = set(&[(1, 3), (10, 12)]);
+        let b = set(&[(2, 5), (7, 8), (13, 20)]);
+        assert_eq!(a.union(&b), set(&[(1, 5), (7, 8), (10, 20)]));
+        assert_eq!(a.union(&b), b.union(&a));
+        assert_eq!(a.union(&IntervalSet::new()), a);
+    }
+
+    #[test]
+    fn intersection() {
+        let a = set(&[(1, 5), (8, 12), (20, 30)]);
+        let b = set(&[(0, 2), (4, 9), (11, 25)]);
+        assert_eq!(
+            a.intersection(&b),
+            set(&[(1, 2), (4, 5), (8, 9), (11, 12), (20, 25)])
+        );
+        assert_eq!(a.intersection(&b), b.intersection(&a));
+        assert!(a.intersection(&set(&[(6, 7), (13, 19)])).is_empty());
+        assert!(a.intersection(&IntervalSet::new()).is_empty());
+    }
+
+    #[test]
+    fn difference() {
+        let a = set(&[(1, 10), (20, 30)]);
+        assert_eq!(
+            a.difference(&set(&[(3, 4), (6, 6), (9, 22)])),
+            set(&[(1, 2), (5, 5), (7, 8), (23, 30)])
+        );
+        assert_eq!(a.difference(&set(&[(0, 40)])), IntervalSet::new());
+        assert_eq!(a.difference(&set(&[(11, 19)])), a);
+        assert_eq!(
+            a.difference(&set(&[(0, 1), (30, 31)])),
+            set(&[(2, 10), (20, 29)])
+        );
+        assert_eq!(a.difference(&IntervalSet::new()), a);
+    }
+
+    #[test]
+    fn interval_from_range_inclusive() {
+        assert_eq!(Interval::try_from(2..=5), Ok(Interval::new(2, 5)));
+        assert_eq!(Interval::try_from(3..=3), Ok(Interval::new(3, 3)));
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = 5..=2;
+        assert!(Interval::try_from(reversed).is_err());
+        let mut exhausted = 3..=3;
+        exhausted.next();
+        assert!(Interval::try_from(exhausted).is_err());
+    }
+
+    #[test]
+    fn range_from_interval() {
+        assert_eq!(Range::try_from(Interval::new(2, 4)), Ok(2..5));
+        assert!(Range::try_from(Interval::new(0, u8::MAX)).is_err());
+    }
+}
@@ -0,0 +1,502 @@
+//! JSON Lines output, and parsing of the JSON requests read by the server
+//! and of the files searched with --json-input.
+//!
+//! Every event is printed as a single JSON object on its own line with a
+//! `type` and a `data` member. A search emits a `begin` event for each file,
+//! followed by its `match` and `context` events in line order, an `end` event
+//! with the statistics for that file and finally a single `summary` event.
+//! The server follows them with an `error` event for each file that could
+//! not be searched.
+
+use crate::stats::Stats;
+use regex::Regex;
+use std::io::{self, Write};
+
+/// Formats the statistics of a search as a JSON object.
+pub fn stats_to_json(stats: Stats) -> String {
+    format!(
+        r#"{{"lines_searched":{},"matched_lines":{},"matches":{},"elapsed":{{"read":{},"match":{},"print":{}}}}}"#,
+        stats.lines_searched,
+        stats.matched_lines,
+        stats.matches,
+        stats.read_time.as_secs_f64(),
+        stats.match_time.as_secs_f64(),
+        stats.print_time.as_secs_f64()
+    )
+}
+
+/// Escapes `s` for use inside a JSON string literal.
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str(r#"\""#),
+            '\\' => escaped.push_str(r"\\"),
+            '\n' => escaped.push_str(r"\n"),
+            '\r' => escaped.push_str(r"\r"),
+            '\t' => escaped.push_str(r"\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Prints the event announcing that `path` is about to be searched.
+pub fn begin(out: &mut impl Write, path: &str) -> io::Result<()> {
+    writeln!(
+        out,
+        r#"{{"type":"begin","data":{{"path":"{}"}}}}"#,
+        escape(path)
+    )
+}
+
+/// Prints a matching line along with the byte span of every match in it.
+/// Whether the line ends with a newline in the file is given by `terminated`.
+pub fn matched(
+    out: &mut impl Write,
+    path: &str,
+    line_number: usize,
+    text: &str,
+    terminated: bool,
+    re: &Regex,
+) -> io::Result<()> {
+    let spans: Vec<String> = re
+        .find_iter(text)
+        .map(|m| {
+            format!(
+                r#"{{"match":"{}","start":{},"end":{}}}"#,
+                escape(m.as_str()),
+                m.start(),
+                m.end()
+            )
+        })
+        .collect();
+
+    line(out, "match", path, line_number, text, terminated, &spans)
+}
+
+/// Prints a line of context surrounding a match.
+pub fn context(
+    out: &mut impl Write,
+    path: &str,
+    line_number: usize,
+    text: &str,
+    terminated: bool,
+) -> io::Result<()> {
+    line(out, "context", path, line_number, text, terminated, &[])
+}
+
+fn line(
+    out: &mut impl Write,
+    kind: &str,
+    path: &str,
+    line_number: usize,
+    text: &str,
+    terminated: bool,
+    spans: &[String],
+) -> io::Result<()> {
+    writeln!(
+        out,
+        r#"{{"type":"{kind}","data":{{"path":"{}","line_number":{line_number},"text":"{}","terminated":{terminated},"submatches":[{}]}}}}"#,
+        escape(path),
+        escape(text),
+        spans.join(",")
+    )
+}
+
+/// Prints the event closing the search of `path`.
+pub fn end(out: &mut impl Write, path: &str, stats: Stats) -> io::Result<()> {
+    writeln!(
+        out,
+        r#"{{"type":"end","data":{{"path":"{}","stats":{}}}}}"#,
+        escape(path),
+        stats_to_json(stats)
+    )
+}
+
+/// Prints the event reporting that `path` could not be searched, for the
+/// server.
+pub fn error(out: &mut impl Write, path: &str, message: &str) -> io::Result<()> {
+    writeln!(
+        out,
+        r#"{{"type":"error","data":{{"path":"{}","message":"{}"}}}}"#,
+        escape(path),
+        escape(message)
+    )
+}
+
+/// Prints the final event with statistics aggregated over every file.
+pub fn summary(
+    out: &mut impl Write,
+    files_searched: usize,
+    files_matched: usize,
+    stats: Stats,
+) -> io::Result<()> {
+    writeln!(
+        out,
+        r#"{{"type":"summary","data":{{"files_searched":{files_searched},"files_matched":{files_matched},"stats":{}}}}}"#,
+        stats_to_json(stats)
+    )
+}
+
+/// A parsed JSON value.
+#[derive(Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Returns the member `key` of an object.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Returns every string within the value, in document order, along with
+    /// its path from the root, such as `.items[3].message`.
+    pub fn strings(&self) -> Vec<(String, &str)> {
+        let mut strings = Vec::new();
+        self.collect_strings(&mut String::new(), &mut strings);
+        strings
+    }
+
+    fn collect_strings<'a>(&'a self, path: &mut String, strings: &mut Vec<(String, &'a str)>) {
+        let len = path.len();
+        match self {
+            Value::String(s) if path.is_empty() => strings.push((".".to_string(), s)),
+            Value::String(s) => strings.push((path.clone(), s)),
+            Value::Array(values) => {
+                for (i, value) in values.iter().enumerate() {
+                    path.push_str(&format!("[{i}]"));
+                    value.collect_strings(path, strings);
+                    path.truncate(len);
+                }
+            }
+            Value::Object(members) => {
+                for (key, value) in members {
+                    let plain = key.starts_with(|c: char| c.is_alphabetic() || c == '_')
+                        && key.chars().all(|c| c.is_alphanumeric() || c == '_');
+                    match plain {
+                        true => path.push_str(&format!(".{key}")),
+                        false => path.push_str(&format!(r#"["{}"]"#, escape(key))),
+                    }
+                    value.collect_strings(path, strings);
+                    path.truncate(len);
+                }
+            }
+            Value::Null | Value::Bool(_) | Value::Number(_) => {}
+        }
+    }
+
+    /// Serializes the value back to JSON text.
+    pub fn to_json(&self) -> String {
+        match self {
+            Value::Null => "null".to_string(),
+            Value::Bool(b) => b.to_string(),
+            Value::Number(n) => n.to_string(),
+            Value::String(s) => format!(r#""{}""#, escape(s)),
+            Value::Array(values) => {
+                let values: Vec<String> = values.iter().map(Value::to_json).collect();
+                format!("[{}]", values.join(","))
+            }
+            Value::Object(members) => {
+                let members: Vec<String> = members
+                    .iter()
+                    .map(|(k, v)| format!(r#""{}":{}"#, escape(k), v.to_json()))
+                    .collect();
+                format!("{{{}}}", members.join(","))
+            }
+        }
+    }
+}
+
+/// Parses a JSON document.
+pub fn parse(s: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        chars: s.chars().peekable(),
+    };
+    let value = parser.value()?;
+    parser.whitespace();
+    match parser.chars.next() {
+        None => Ok(value),
+        Some(c) => Err(format!("unexpected '{c}' after value")),
+    }
+}
+
+/// Parses `text` as a single JSON document, or failing that, as JSON Lines
+/// with a document on every line that is not blank. Returns each document
+/// along with the number and offset of the line it starts on.
+pub fn parse_documents(text: &str) -> Result<Vec<(usize, usize, Value)>, String> {
+    if let Ok(value) = parse(text) {
+        return Ok(vec![(1, 0, value)]);
+    }
+    let mut documents = Vec::new();
+    let mut offset = 0;
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        if !line.trim().is_empty() {
+            let value = parse(line).map_err(|e| format!("line {}: {e}", i + 1))?;
+            documents.push((i + 1, offset, value));
+        }
+        offset += line.len();
+    }
+    Ok(documents)
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("expected '{expected}', found '{c}'")),
+            None => Err(format!("expected '{expected}', found end of input")),
+        }
+    }
+
+    fn literal(&mut self, literal: &str, value: Value) -> Result<Value, String> {
+        for expected in literal.chars() {
+            self.expect(expected)?;
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.whitespace();
+        match self.chars.peek() {
+            Some('n') => self.literal("null", Value::Null),
+            Some('t') => self.literal("true", Value::Bool(true)),
+            Some('f') => self.literal("false", Value::Bool(false)),
+            Some('"') => self.string().map(Value::String),
+            Some('[') => self.array(),
+            Some('{') => self.object(),
+            Some(c) if *c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) => Err(format!("unexpected '{c}'")),
+            None => Err("unexpected end of input".to_string()),
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let mut number = String::new();
+        while let Some(c) = self
+            .chars
+            .next_if(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+        {
+            number.push(c);
+        }
+        number
+            .parse()
+            .map(Value::Number)
+            .map_err(|_| format!("invalid number '{number}'"))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => match self.chars.next() {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('/') => s.push('/'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('u') => s.push(self.unicode_escape()?),
+                    Some(c) => return Err(format!("invalid escape '\\{c}'")),
+                    None => return Err("unterminated string".to_string()),
+                },
+                Some(c) => s.push(c),
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+    }
+
+    /// Parses the hex digits of a `\u` escape, combining surrogate pairs.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        let code = if (0xd800..0xdc00).contains(&high) {
+            self.expect('\\')?;
+            self.expect('u')?;
+            let low = self.hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(format!("invalid low surrogate {low:#x}"));
+            }
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| format!("invalid code point {code:#x}"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits: String = (0..4).filter_map(|_| self.chars.next()).collect();
+        u32::from_str_radix(&digits, 16).map_err(|_| format!("invalid unicode escape '{digits}'"))
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        self.whitespace();
+        if self.chars.next_if_eq(&']').is_some() {
+            return Ok(Value::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            self.whitespace();
+            match self.chars.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(Value::Array(values)),
+                _ => return Err("expected ',' or ']' in array".to_string()),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.expect('{')?;
+        let mut members = Vec::new();
+        self.whitespace();
+        if self.chars.next_if_eq(&'}').is_some() {
+            return Ok(Value::Object(members));
+        }
+        loop {
+            self.whitespace();
+            let key = self.string()?;
+            self.whitespace();
+            self.expect(':')?;
+            members.push((key, self.value()?));
+            self.whitespace();
+            match self.chars.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(Value::Object(members)),
+                _ => return Err("expected ',' or '}' in object".to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Source;
+    use regex::Regex;
+    use std::io::Cursor;
+
+    #[test]
+    fn parse_values() {
+        assert_eq!(parse(" null "), Ok(Value::Null));
+        assert_eq!(parse("true"), Ok(Value::Bool(true)));
+        assert_eq!(parse("-1.5e2"), Ok(Value::Number(-150.0)));
+        assert_eq!(
+            parse(r#"{"a": [1, "x"], "b": {}}"#),
+            Ok(Value::Object(vec![
+                (
+                    "a".to_string(),
+                    Value::Array(vec![Value::Number(1.0), Value::String("x".to_string())])
+                ),
+                ("b".to_string(), Value::Object(Vec::new())),
+            ]))
+        );
+    }
+
+    #[test]
+    fn parse_escapes() {
+        let text = r#""q\" b\\ s\/ \n\t é 😀""#;
+        assert_eq!(
+            parse(text),
+            Ok(Value::String("q\" b\\ s/ \n\t é 😀".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_documents() {
+        for text in [
+            "",
+            "nul",
+            "[1,",
+            "[1 2]",
+            r#"{"a" 1}"#,
+            r#"{a: 1}"#,
+            r#""open"#,
+            r#""\x""#,
+            r#""\u12""#,
+            r#""\ud83dA""#,
+            "1 2",
+            "--1",
+        ] {
+            assert!(parse(text).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn string_paths() {
+        let value =
+            parse(r#"{"msg": "a", "items": [{"x": "b"}, 2, "c"], "odd key": "d", "n": null}"#)
+                .unwrap();
+        assert_eq!(
+            value.strings(),
+            [
+                (".msg".to_string(), "a"),
+                (".items[0].x".to_string(), "b"),
+                (".items[2]".to_string(), "c"),
+                (r#"["odd key"]"#.to_string(), "d"),
+            ]
+        );
+        assert_eq!(
+            parse(r#""top""#).unwrap().strings(),
+            [(".".to_string(), "top")]
+        );
+    }
+
+    #[test]
+    fn single_document_over_several_lines() {
+        let documents = parse_documents("{\n  \"a\": \"x\"\n}\n").unwrap();
+        assert_eq!(documents.len(), 1);
+        assert_eq!((documents[0].0, documents[0].1), (1, 0));
+    }
+
+    #[test]
+    fn json_lines() {
+        let documents = parse_documents("{\"a\":1}\n\n{\"a\":2}\n").unwrap();
+        let positions: Vec<(usize, usize)> = documents.iter().map(|(n, o, _)| (*n, *o)).collect();
+        assert_eq!(positions, [(1, 0), (3, 9)]);
+        assert_eq!(
+            parse_documents("{\"a\":1}\n{oops}\n").err().as_deref(),
+            Some("line 2: expected '\"', found 'o'")
+        );
+    }
+
+    #[test]
+    fn matching_values() {
+        let text =
+            "{\"level\":\"error\",\"msg\":\"disk full\"}\n{\"msg\":\"ok\",\"tags\":[\"disk\"]}\n";
+        let source = Source::Bytes(Cursor::new(text.as_bytes().to_vec()));
+        let re = Regex::new("disk").unwrap();
+        let (lines, stats) = crate::find_json_values(source, &re, 1, 0, 0).unwrap();
+        let lines: Vec<(usize, &str)> = lines
+            .iter()
+            .map(|line| (line.number, line.text.as_ref()))
+            .collect();
+        assert_eq!(lines, [(1, ".msg: disk full"), (2, ".tags[0]: disk")]);
+        assert_eq!(
+            (stats.lines_searched, stats.matched_lines, stats.matches),
+            (4, 2, 2)
+        );
+    }
+}
@@ -0,0 +1,92 @@
+//! Detection of the lines starting a function or section, per language.
+//!
+//! The language of a file is guessed from its extension. Each language has a
+//! pattern for the lines that start a definition, such as `fn`, `def`,
+//! `class` or `impl` headers, and files of other languages fall back to a
+//! heuristic that suits most of them.
+
+/// A language and the lines starting its sections.
+struct Language {
+    /// Extensions, without the leading dot, of files in this language.
+    extensions: &'static [&'static str],
+    /// Matches the first line of a function or section.
+    header: &'static str,
+}
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        extensions: &["rs"],
+        header: concat!(
+            r"^\s*(?:pub(?:\([^)]*\))?\s+)?",
+            r#"(?:(?:async|const|unsafe|default|extern(?:\s+"[^"]*")?)\s+)*"#,
+            r"(?:fn|impl|struct|enum|union|trait|mod|macro_rules!)\b",
+        ),
+    },
+    Language {
+        extensions: &["py", "pyi"],
+        header: r"^\s*(?:async\s+)?(?:def|class)\b",
+    },
+    Language {
+        extensions: &["c", "h", "cc", "cpp", "cxx", "hpp", "hh"],
+        header: r"^(?:[[:alpha:]_][^=;]*\(|(?:class|struct|namespace)\b)",
+    },
+    Language {
+        extensions: &["go"],
+        header: r"^(?:func\b|type\s+\w+\s+(?:struct|interface)\b)",
+    },
+    Language {
+        extensions: &["js", "jsx", "mjs", "ts", "tsx"],
+        header: concat!(
+            r"^\s*(?:export\s+)?(?:default\s+)?(?:async\s+)?",
+            r"(?:function\b|class\b|(?:const|let|var)\s+\w+\s*=\s*(?:async\s*)?\()",
+        ),
+    },
+    Language {
+        extensions: &["java", "cs", "kt", "scala"],
+        header: concat!(
+            r"^\s*(?:(?:public|private|protected|internal|static|final|abstract|override)\s+)*",
+            r"(?:class\b|interface\b|enum\b|fun\b|[\w<>\[\],]+\s+\w+\s*\()",
+        ),
+    },
+    Language {
+        extensions: &["rb"],
+        header: r"^\s*(?:def|class|module)\b",
+    },
+    Language {
+        extensions: &["sh", "bash", "zsh"],
+        header: r"^\s*(?:function\s+\w+|\w+\s*\(\s*\))",
+    },
+    Language {
+        extensions: &["md", "markdown"],
+        header: r"^#{1,6}\s",
+    },
+    Language {
+        extensions: &["ini", "toml", "cfg", "conf"],
+        header: r"^\s*\[",
+    },
+];
+
+/// Heuristic for files of other languages: a declaration starting with an
+/// identifier and opening a parameter list, as in C, a definition introduced
+/// by a common keyword at any indentation, a Markdown heading or an INI
+/// section.
+const DEFAULT_HEADER: &str = concat!(
+    r"^(?:[[:alpha:]_$][^=;]*\(",
+    r"|\s*(?:(?:pub(?:\([^)]*\))?|export|async|static|public|private|protected)\s+)*",
+    r"(?:fn|def|func|function|class|impl|struct|enum|trait|interface)\b",
+    r"|#{1,6}\s",
+    r"|\[.+\]\s*$)",
+);
+
+/// Returns the pattern matching the first line of a function or section in
+/// the file at `path`.
+pub fn header(path: &str) -> &'static str {
+    let Some((_, extension)) = path.rsplit_once('.') else {
+        return DEFAULT_HEADER;
+    };
+    let extension = extension.to_ascii_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|language| language.extensions.contains(&extension.as_str()))
+        .map_or(DEFAULT_HEADER, |language| language.header)
+}
@@ -0,0 +1,6 @@
+//! Building blocks of the search that are also usable as a library.
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod interval;
+pub mod lines;
@@ -0,0 +1,238 @@
+//! Splitting of a file into lines as it is read, shared by the search and
+//! the C API.
+
+use memchr::memchr;
+use std::io::{self, ErrorKind, Read};
+
+/// Size of the buffer lines are read into, which grows to hold longer lines.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Splits a file into lines, reading it in large blocks into a buffer that
+/// is reused for every line and locating newlines with `memchr`. Lines longer
+/// than a maximum size are skipped rather than held in memory.
+pub struct LineReader<R> {
+    reader: R,
+    buf: Vec<u8>,
+    /// The unread bytes are `buf[start..end]`.
+    start: usize,
+    end: usize,
+    /// Number of bytes searched for a newline past `start`, so that they are
+    /// not searched again once more bytes are read.
+    searched: usize,
+    eof: bool,
+    /// Lines longer than this many bytes are skipped.
+    max_line: usize,
+    /// Whether the bytes being read belong to a line that is too long, and
+    /// are dropped up to its end.
+    skipping: bool,
+    /// Number of lines skipped for being too long.
+    skipped: usize,
+    /// Number of the last line returned or skipped.
+    number: usize,
+    /// Offset in the file of `buf[start]`.
+    offset: usize,
+    /// Whether the last line returned ended with a newline.
+    terminated: bool,
+    /// The error that ended reading early, if any.
+    error: Option<io::Error>,
+    /// Number of lines left to read.
+    remaining: usize,
+}
+
+impl<R: Read> LineReader<R> {
+    /// Returns a reader of at most `line_count` lines of `reader`, starting
+    /// at line `first_line`, that skips lines longer than `max_line` bytes.
+    /// The first byte of `reader` is at `offset` in the file. Lines are
+    /// numbered from 1, and a `first_line` of 0 is taken as 1.
+    pub fn new(
+        reader: R,
+        offset: usize,
+        first_line: usize,
+        line_count: usize,
+        max_line: usize,
+    ) -> LineReader<R> {
+        let mut lines = LineReader {
+            reader,
+            buf: vec![0; BUFFER_SIZE],
+            start: 0,
+            end: 0,
+            searched: 0,
+            eof: false,
+            max_line,
+            skipping: false,
+            skipped: 0,
+            number: 0,
+            offset,
+            terminated: true,
+            error: None,
+            remaining: first_line.saturating_sub(1),
+        };
+        while lines.next_line().is_some() {}
+        lines.remaining = line_count;
+        lines.skipped = 0;
+        lines
+    }
+
+    /// Returns the number and offset of the next line along with the line,
+    /// without its terminating `\n` or `\r\n`. Lines that are too long are
+    /// skipped.
+    pub fn next_line(&mut self) -> Option<(usize, usize, &[u8])> {
+        let (end, next) = loop {
+            if self.remaining == 0 {
+                return None;
+            }
+
+            let unsearched = &self.buf[self.start + self.searched..self.end];
+            if let Some(i) = memchr(b'\n', unsearched) {
+                let end = self.start + self.searched + i;
+                if self.skipping || end - self.start > self.max_line {
+                    self.skip_line(end + 1);
+                    continue;
+                }
+                break (end, end + 1);
+            }
+            self.searched = self.end - self.start;
+            if self.eof {
+                if self.skipping || self.searched > self.max_line {
+                    self.skip_line(self.end);
+                    continue;
+                }
+                if self.start == self.end {
+                    return None;
+                }
+                break (self.end, self.end);
+            }
+            if self.searched > self.max_line {
+                // Only the end of a line that is too long is needed, so the
+                // rest of it is dropped as it is read.
+                self.skipping = true;
+                self.offset += self.end - self.start;
+                self.start = self.end;
+                self.searched = 0;
+            }
+            self.fill();
+        };
+
+        self.remaining -= 1;
+        self.number += 1;
+        self.searched = 0;
+        self.terminated = next > end;
+        let mut line = &self.buf[self.start..end];
+        // A carriage return is only part of the line terminator when it is
+        // followed by a newline.
+        if next > end {
+            line = line.strip_suffix(b"\r").unwrap_or(line);
+        }
+        let offset = self.offset;
+        self.offset += next - self.start;
+        self.start = next;
+        Some((self.number, offset, line))
+    }
+
+    /// Returns the number of the last line returned or skipped.
+    pub fn number(&self) -> usize {
+        self.number
+    }
+
+    /// Returns the offset in the file of the first byte not read as part of a
+    /// line yet.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the number of lines skipped for being too long.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Skips the line that is too long, which ends at `next`.
+    fn skip_line(&mut self, next: usize) {
+        self.offset += next - self.start;
+        self.start = next;
+        self.searched = 0;
+        self.skipping = false;
+        self.remaining -= 1;
+        self.number += 1;
+        self.skipped += 1;
+    }
+
+    /// Returns whether the last line returned ended with a newline, which
+    /// only the last line of a file can lack.
+    pub fn terminated(&self) -> bool {
+        self.terminated
+    }
+
+    /// Returns the error that ended reading early, if any. The lines read up
+    /// to that point are only part of the file.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    /// Reads more of the file, moving the unread bytes to the front of the
+    /// buffer first and growing it if they fill it. An error ends reading as
+    /// if the end of the file was reached, and is kept for `take_error`.
+    fn fill(&mut self) {
+        if self.start > 0 {
+            self.buf.copy_within(self.start..self.end, 0);
+            self.end -= self.start;
+            self.start = 0;
+        }
+        if self.end == self.buf.len() {
+            self.buf.resize(self.buf.len() * 2, 0);
+        }
+        let n = loop {
+            match self.reader.read(&mut self.buf[self.end..]) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.error = Some(e);
+                    break 0;
+                }
+            }
+        };
+        self.end += n;
+        self.eof = n == 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the numbers, offsets and text of the lines read from `text`.
+    fn read(text: &str, first_line: usize, line_count: usize) -> Vec<(usize, usize, String)> {
+        let mut lines = LineReader::new(text.as_bytes(), 0, first_line, line_count, usize::MAX);
+        let mut read = Vec::new();
+        while let Some((number, offset, line)) = lines.next_line() {
+            read.push((number, offset, String::from_utf8_lossy(line).into_owned()));
+        }
+        read
+    }
+
+    #[test]
+    fn reads_every_line() {
+        let lines = read("one\r\ntwo\nthree", 1, usize::MAX);
+        assert_eq!(
+            lines,
+            [
+                (1, 0, "one".to_string()),
+                (2, 5, "two".to_string()),
+                (3, 9, "three".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn starts_at_the_first_line() {
+        let lines = read("one\ntwo\nthree\n", 2, 1);
+        assert_eq!(lines, [(2, 4, "two".to_string())]);
+    }
+
+    #[test]
+    fn takes_line_zero_as_line_one() {
+        assert_eq!(
+            read("one\ntwo\n", 0, usize::MAX),
+            read("one\ntwo\n", 1, usize::MAX)
+        );
+    }
+}
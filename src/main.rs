@@ -1,62 +1,3343 @@
-use clap::Parser;
+mod cache;
+mod checkpoint;
+mod config;
+mod csv;
+mod decoder;
+mod diagnostic;
+mod diff;
+mod edit;
+mod encoding;
+mod expr;
+mod follow;
+mod help;
+mod hexdump;
+mod json;
+mod lang;
+mod messages;
+mod mmap;
+mod prefetch;
+mod printer;
+mod record;
+mod remote;
+mod replace;
+mod report;
+mod s3;
+mod server;
+mod stats;
+mod stream;
+mod time;
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+mod uring;
+mod walk;
+
+use checkpoint::Checkpoint;
+use clap::{ColorChoice, Parser, Subcommand, ValueEnum};
 use core::panic;
-use regex::Regex;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use encoding::Encoding;
+use expr::Expr;
+use follow::Follower;
+use grep::interval::{Interval, IntervalSet};
+use grep::lines::LineReader;
+#[cfg(not(target_family = "wasm"))]
+use memmap2::Mmap;
+use prefetch::IoMode;
+use printer::{Gutter, Highlighter, Line, Width};
+use regex::{bytes, Regex};
+use replace::Template;
+use report::{OutputFormat, Report};
+use stats::Stats;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Cursor, IsTerminal, Read, Seek, SeekFrom, Write};
+use std::iter;
+use std::mem;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::process;
+#[cfg(not(feature = "rayon"))]
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use stream::Deadline;
+use walk::{DeviceAction, DirectoryAction, IgnoreOptions};
 
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
+#[command(
+    version,
+    about,
+    long_about = help::LONG_ABOUT,
+    after_long_help = help::examples(),
+    args_conflicts_with_subcommands = true,
+    args_override_self = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// The options of `search`, which runs when no command is given.
+    #[command(flatten)]
+    search: Args,
+}
+
+/// Where the patterns are matched with --all-match and --expr.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Scope {
+    /// Anywhere in the file.
+    File,
+    /// On the same line.
+    Line,
+}
+
+impl Cli {
+    /// Returns the options of the search to run, or the command when it is
+    /// not a search.
+    fn into_search(self) -> Result<Args, Command> {
+        match self.command {
+            None => Ok(self.search),
+            Some(Command::Search(args)) => Ok(*args),
+            Some(Command::Replace(replace)) => Ok(Args {
+                replace: Some(replace.template),
+                ..replace.search
+            }),
+            Some(command) => Err(command),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Search files for lines matching a pattern. This is the default.
+    Search(Box<Args>),
+    /// Print the files that would be searched, without searching them.
+    Files(FilesArgs),
+    /// Print matching lines with every match replaced by a template.
+    Replace(Box<ReplaceArgs>),
+    /// Print the document types that can be decoded to text.
+    Types,
+}
+
+#[derive(clap::Args)]
 struct Args {
-    /// FILE to be searched.
+    /// FILE to be searched. May be given more than once. `-`, or giving no
+    /// file at all, searches stdin. With the http feature, an http:// or
+    /// https:// URL downloads the file first, and with the s3 feature,
+    /// s3://bucket/prefix searches every object whose key starts with prefix.
     #[arg(short, long, value_name = "FILE")]
-    file: Option<String>,
+    file: Vec<String>,
+
+    /// Use PATTERN as the pattern. It may start with a dash, as in
+    /// `-e -foo`, or be given after `--` instead. When given more than once,
+    /// a line matches when it matches any of the patterns.
+    #[arg(
+        short = 'e',
+        long,
+        value_name = "PATTERN",
+        allow_hyphen_values = true,
+        help_heading = help::MATCHING
+    )]
+    pattern: Vec<String>,
+
+    /// Only report a file when every pattern matches one of its lines, or
+    /// with `line`, only report the lines that every pattern matches.
+    #[arg(
+        long,
+        value_name = "WHERE",
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "file",
+        conflicts_with = "hexdump",
+        help_heading = help::MATCHING
+    )]
+    all_match: Option<Scope>,
+
+    /// Match the lines for which EXPR holds, where EXPR combines patterns
+    /// with AND, OR, NOT and parentheses, as in `foo AND (bar OR baz) NOT
+    /// qux`. Patterns containing spaces or parentheses are quoted.
+    #[arg(
+        long,
+        value_name = "EXPR",
+        value_parser = Expr::parse,
+        conflicts_with_all = ["pattern", "trailing_pattern", "pattern_file", "all_match", "hexdump"],
+        help_heading = help::MATCHING
+    )]
+    expr: Option<Expr>,
+
+    /// Evaluate --expr on each line, or on each file, where a pattern holds
+    /// when it matches any of its lines.
+    #[arg(
+        long,
+        value_name = "WHERE",
+        value_enum,
+        default_value_t = Scope::Line,
+        requires = "expr",
+        help_heading = help::MATCHING
+    )]
+    expr_scope: Scope,
 
-    /// Use PATTERN as the pattern.
-    #[arg(short, long, value_name = "PATTERN")]
-    pattern: Option<String>,
+    /// The patterns of --expr, or of --all-match, compiled one by one.
+    #[arg(skip)]
+    patterns: Vec<Regex>,
+
+    /// Parse each file as a JSON document, or as JSON Lines when it is not
+    /// one, and match the pattern against its string values, each printed
+    /// after its path, such as `.items[3].message`.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "max_count", "mmap", "hexdump", "function_context", "show_heading",
+            "record_separator", "paragraph", "byte_range", "line_range"
+        ],
+        help_heading = help::MATCHING
+    )]
+    json_input: bool,
+
+    /// Parse each file as CSV, or as TSV when its name ends in `.tsv`, where
+    /// quoted fields may hold delimiters and newlines, and print the rows in
+    /// which the pattern matches one of the fields. The first row names the
+    /// columns and is not searched.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "max_count", "mmap", "hexdump", "function_context", "show_heading",
+            "record_separator", "paragraph", "byte_range", "line_range", "json_input"
+        ],
+        help_heading = help::MATCHING
+    )]
+    csv: bool,
+
+    /// With --csv, match the pattern only against COLUMNS, a comma-separated
+    /// list of column names or numbers counted from 1.
+    #[arg(
+        long,
+        value_name = "COLUMNS",
+        value_delimiter = ',',
+        requires = "csv",
+        help_heading = help::MATCHING
+    )]
+    columns: Vec<String>,
+
+    /// With --csv, separate the fields with CHAR rather than a comma, or a
+    /// tab in TSV files.
+    #[arg(long, value_name = "CHAR", requires = "csv", help_heading = help::MATCHING)]
+    delimiter: Option<char>,
+
+    /// Match the pattern against the paths of the files rather than their
+    /// contents, and print the matching paths. Directories among the files,
+    /// or the current directory when none is given, are searched recursively.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "expr", "all_match", "json", "hexdump", "hex", "count", "only_matching", "replace",
+            "unique", "output_format", "histogram", "group_by", "record_separator", "paragraph",
+            "json_input", "csv"
+        ],
+        help_heading = help::MATCHING
+    )]
+    path_match: bool,
+
+    /// Search for the bytes given as HEX, such as `DE AD ?? EF`, where `??`
+    /// stands for any byte, printing every match as with --hexdump.
+    #[arg(
+        long,
+        value_name = "HEX",
+        value_parser = hexdump::parse_pattern,
+        conflicts_with_all = [
+            "pattern", "trailing_pattern", "pattern_file", "expr", "all_match", "ignore_case",
+            "smart_case", "json", "only_matching", "replace", "function_context", "show_heading",
+            "since", "until", "unique", "files_with_matches", "quiet", "count", "output_format",
+            "histogram", "group_by", "record_separator", "paragraph", "json_input", "csv"
+        ],
+        help_heading = help::MATCHING
+    )]
+    hex: Option<String>,
+
+    /// Match letters regardless of their case. Of --ignore-case,
+    /// --case-sensitive and --smart-case, the last one given applies.
+    #[arg(
+        short = 'i',
+        long,
+        overrides_with_all = ["case_sensitive", "smart_case"],
+        help_heading = help::MATCHING
+    )]
+    ignore_case: bool,
+
+    /// Match letters only in the case given in the pattern. This is the
+    /// default.
+    #[arg(
+        long,
+        overrides_with_all = ["ignore_case", "smart_case"],
+        help_heading = help::MATCHING
+    )]
+    case_sensitive: bool,
+
+    /// Match letters regardless of their case, unless the pattern contains an
+    /// uppercase letter.
+    #[arg(
+        short = 'S',
+        long,
+        overrides_with_all = ["ignore_case", "case_sensitive"],
+        help_heading = help::MATCHING
+    )]
+    smart_case: bool,
+
+    /// PATTERN given after the `--` marking the end of the options.
+    #[arg(
+        last = true,
+        value_name = "PATTERN",
+        conflicts_with = "pattern",
+        help_heading = help::MATCHING
+    )]
+    trailing_pattern: Option<String>,
+
+    /// Read patterns from FILE, one per line, or from standard input when
+    /// FILE is `-`. A line matches when it matches any of the patterns,
+    /// including the one given with --pattern.
+    #[arg(long, value_name = "FILE", help_heading = help::MATCHING)]
+    pattern_file: Option<String>,
 
     /// Print NUM lines of trailing context after matching lines.
-    #[arg(short, long, value_name = "NUM")]
+    #[arg(
+        short = 'A',
+        long,
+        short_alias = 'a',
+        value_name = "NUM",
+        help_heading = help::OUTPUT
+    )]
     after_context: Option<usize>,
 
     /// Print NUM lines of leading context before matching lines.
-    #[arg(short, long, value_name = "NUM")]
+    #[arg(short = 'B', long, value_name = "NUM", help_heading = help::OUTPUT)]
     before_context: Option<usize>,
+
+    /// Extend the context of each match back to the start of the function or
+    /// section containing it, guessed from lines such as `fn name(`, `def
+    /// name:` or `## Heading`.
+    #[arg(
+        short = 'W',
+        long,
+        conflicts_with_all = ["max_count", "mmap", "hexdump", "expr", "all_match"],
+        help_heading = help::OUTPUT
+    )]
+    function_context: bool,
+
+    /// Print the line starting the function or section containing each block
+    /// of lines above it, marked with `=` rather than `:`, unless the block
+    /// already includes it.
+    #[arg(
+        long,
+        conflicts_with_all = ["max_count", "mmap", "hexdump", "expr", "all_match", "json"],
+        help_heading = help::OUTPUT
+    )]
+    show_heading: bool,
+
+    /// Split the input into records, each starting at a line matching REGEX
+    /// and running up to the next one, such as log entries followed by their
+    /// stack trace. The pattern is matched against each record as a whole,
+    /// its lines joined by newlines, and matching records are printed whole.
+    #[arg(
+        long,
+        value_name = "REGEX",
+        value_parser = Regex::new,
+        conflicts_with_all = [
+            "max_count", "mmap", "hexdump", "expr", "all_match", "function_context",
+            "show_heading", "since", "until"
+        ],
+        help_heading = help::OUTPUT
+    )]
+    record_separator: Option<Regex>,
+
+    /// Split the input into paragraphs separated by blank lines, and print
+    /// every paragraph in which the pattern matches whole, like
+    /// --record-separator.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "record_separator", "max_count", "mmap", "hexdump", "expr", "all_match",
+            "function_context", "show_heading", "since", "until"
+        ],
+        help_heading = help::OUTPUT
+    )]
+    paragraph: bool,
+
+    /// Print NUM lines of context both before and after matching lines,
+    /// unless -A or -B says otherwise. With any context, a `--` line
+    /// separates the blocks of lines that do not follow one another.
+    #[arg(short = 'C', long, value_name = "NUM", help_heading = help::OUTPUT)]
+    context: Option<usize>,
+
+    /// Print the number of each line before it. This is the default when
+    /// printing to a terminal.
+    #[arg(short = 'n', long, overrides_with = "no_line_number", help_heading = help::OUTPUT)]
+    line_number: bool,
+
+    /// Never print line numbers.
+    #[arg(short = 'N', long, overrides_with = "line_number", help_heading = help::OUTPUT)]
+    no_line_number: bool,
+
+    /// Right-align line numbers in a gutter WIDTH columns wide, or with
+    /// `auto`, as wide as the largest number printed for each file.
+    #[arg(
+        long,
+        value_name = "WIDTH",
+        value_parser = Width::parse,
+        help_heading = help::OUTPUT
+    )]
+    line_number_width: Option<Width>,
+
+    /// Separate the number or offset of each line from its text with SEP.
+    #[arg(
+        long,
+        value_name = "SEP",
+        default_value = ": ",
+        help_heading = help::OUTPUT
+    )]
+    line_number_separator: String,
+
+    /// Print the name of each file above its lines instead of on every line.
+    /// This is the default when printing to a terminal.
+    #[arg(long, overrides_with = "no_heading", help_heading = help::OUTPUT)]
+    heading: bool,
+
+    /// Print the name of each file on every line, even in a terminal.
+    #[arg(long, overrides_with = "heading", help_heading = help::OUTPUT)]
+    no_heading: bool,
+
+    /// Print the directories of each path separated by CHAR, such as `\`,
+    /// rather than as given.
+    #[arg(long, value_name = "CHAR", help_heading = help::OUTPUT)]
+    path_separator: Option<char>,
+
+    /// Separate the name of each file from what follows it on the same line
+    /// with SEP, such as a tab, rather than with `:`.
+    #[arg(
+        long,
+        value_name = "SEP",
+        default_value = ":",
+        help_heading = help::OUTPUT
+    )]
+    filename_separator: String,
+
+    /// Print the offset in bytes of each line within its file before it, or
+    /// of each match with --only-matching.
+    #[arg(short = 'b', long, help_heading = help::OUTPUT)]
+    byte_offset: bool,
+
+    /// Search only bytes START through END (inclusive) of the file. Either
+    /// bound may be omitted. Line numbers are counted from START.
+    #[arg(
+        long,
+        value_name = "START..END",
+        value_parser = parse_range,
+        help_heading = help::FILTERING
+    )]
+    byte_range: Option<RangeInclusive<usize>>,
+
+    /// Search only lines START through END (inclusive). Either bound may be
+    /// omitted. Context is not printed from outside the range.
+    #[arg(
+        long,
+        value_name = "START..END",
+        value_parser = parse_range,
+        help_heading = help::FILTERING
+    )]
+    line_range: Option<RangeInclusive<usize>>,
+
+    /// Print results as a stream of JSON Lines events.
+    #[arg(long, help_heading = help::OUTPUT)]
+    json: bool,
+
+    /// Print the total number of matches and files to stderr once the search
+    /// is complete.
+    #[arg(long, help_heading = help::OUTPUT)]
+    summary: bool,
+
+    /// Print statistics about the search, including the time spent in each
+    /// phase and by each worker, to stderr once the search is complete. With
+    /// `json`, they are printed as a single JSON object.
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "table",
+        help_heading = help::OUTPUT
+    )]
+    stats: Option<stats::Format>,
+
+    /// Print a table of the files containing a match, the most matches
+    /// first, along with the total, to stderr once the search is complete.
+    #[arg(long, help_heading = help::OUTPUT)]
+    count_summary: bool,
+
+    /// Buffer at most SIZE bytes of output before writing it out. SIZE may
+    /// have a K, M or G suffix.
+    #[arg(
+        long,
+        value_name = "SIZE",
+        value_parser = parse_size,
+        default_value = "8K",
+        help_heading = help::PERFORMANCE
+    )]
+    max_buffer: usize,
+
+    /// Skip lines longer than SIZE bytes with a warning, rather than holding
+    /// them in memory. SIZE may have a K, M or G suffix.
+    #[arg(
+        long,
+        value_name = "SIZE",
+        value_parser = parse_size,
+        default_value = "256M",
+        help_heading = help::PERFORMANCE
+    )]
+    max_line_size: usize,
+
+    /// Exit with a non-zero status unless at least NUM matches were found
+    /// across all files.
+    #[arg(long, value_name = "NUM", help_heading = help::MATCHING)]
+    require_count: Option<usize>,
+
+    /// Search files with a pool of NUM worker threads. A single file is
+    /// instead split into NUM line-aligned chunks that are searched in
+    /// parallel.
+    #[arg(
+        short = 'j',
+        long,
+        value_name = "NUM",
+        default_value_t = 1,
+        help_heading = help::PERFORMANCE
+    )]
+    threads: usize,
+
+    /// Search memory-mapped files, locating lines only around matches rather
+    /// than splitting the whole file into lines. Without --mmap or --no-mmap,
+    /// large files are mapped unless they are on a network filesystem or
+    /// would not fit in the memory available.
+    #[arg(
+        long,
+        overrides_with = "no_mmap",
+        conflicts_with_all = ["line_range", "encoding"],
+        help_heading = help::PERFORMANCE
+    )]
+    mmap: bool,
+
+    /// Never memory-map files, splitting them into lines instead.
+    #[arg(long, overrides_with = "mmap", help_heading = help::PERFORMANCE)]
+    no_mmap: bool,
+
+    /// Read files with blocking reads one at a time (sync), or keep many
+    /// reads in flight ahead of the search (async), which is faster on
+    /// network filesystems. The async mode requires the tokio feature.
+    #[arg(
+        long,
+        value_name = "MODE",
+        value_enum,
+        default_value_t = IoMode::Sync,
+        help_heading = help::PERFORMANCE
+    )]
+    io: IoMode,
+
+    /// Search every file below the directories among the files, like
+    /// `--directories recurse`.
+    #[arg(short = 'r', long, help_heading = help::FILTERING)]
+    recursive: bool,
+
+    /// What to do with directories among the files: read them, which fails,
+    /// search every file below them, or skip them.
+    #[arg(
+        short = 'd',
+        long,
+        value_name = "ACTION",
+        value_enum,
+        default_value_t = DirectoryAction::Read,
+        help_heading = help::FILTERING
+    )]
+    directories: DirectoryAction,
+
+    /// What to do with FIFOs, sockets and device nodes among the files: read
+    /// them, which may block until they are written to, or skip them.
+    #[arg(
+        short = 'D',
+        long,
+        value_name = "ACTION",
+        value_enum,
+        default_value_t = DeviceAction::Read,
+        help_heading = help::FILTERING
+    )]
+    devices: DeviceAction,
+
+    #[command(flatten)]
+    ignore: IgnoreOptions,
+
+    /// Only search files modified after TIME, given either as a duration
+    /// before now (e.g. 2d, 90m) or as a date (YYYY-MM-DD).
+    #[arg(
+        long,
+        value_name = "TIME",
+        value_parser = time::parse_time,
+        help_heading = help::FILTERING
+    )]
+    newer_than: Option<SystemTime>,
+
+    /// Only search files modified before TIME, given either as a duration
+    /// before now (e.g. 2d, 90m) or as a date (YYYY-MM-DD).
+    #[arg(
+        long,
+        value_name = "TIME",
+        value_parser = time::parse_time,
+        help_heading = help::FILTERING
+    )]
+    older_than: Option<SystemTime>,
+
+    /// Only report the matching lines whose timestamp is at or after TIME,
+    /// given as a duration before now (e.g. 2h), a date (YYYY-MM-DD) or a
+    /// date and time (YYYY-MM-DDTHH:MM or YYYY-MM-DDTHH:MM:SS) in UTC.
+    #[arg(
+        long,
+        value_name = "TIME",
+        value_parser = time::parse_time,
+        conflicts_with_all = ["hexdump", "function_context", "show_heading"],
+        help_heading = help::FILTERING
+    )]
+    since: Option<SystemTime>,
+
+    /// Only report the matching lines whose timestamp is at or before TIME,
+    /// given like the time of --since.
+    #[arg(
+        long,
+        value_name = "TIME",
+        value_parser = time::parse_time,
+        conflicts_with_all = ["hexdump", "function_context", "show_heading"],
+        help_heading = help::FILTERING
+    )]
+    until: Option<SystemTime>,
+
+    /// Find the timestamp of a line for --since and --until with REGEX, whose
+    /// named groups year, month (a number or an English name), day and
+    /// optionally hour, minute and second hold the time in UTC, or else whose
+    /// group epoch holds the seconds since 1970. Lines without a timestamp are
+    /// not reported. Defaults to ISO 8601 timestamps.
+    #[arg(
+        long,
+        value_name = "REGEX",
+        value_parser = time::parse_timestamp_pattern,
+        default_value = time::ISO_TIMESTAMP,
+        hide_default_value = true,
+        help_heading = help::FILTERING
+    )]
+    timestamp_pattern: Regex,
+
+    /// Leave out the default options read from the .rustlerc file in the
+    /// current directory or the nearest of its parents, and from the user's
+    /// $XDG_CONFIG_HOME/rustle/config.
+    #[arg(long)]
+    no_config: bool,
+
+    /// Run as a server answering JSON-RPC search requests, one per line, on
+    /// stdin and stdout.
+    #[arg(long)]
+    server: bool,
+
+    /// Listen for server connections on the unix socket PATH rather than
+    /// using stdin and stdout.
+    #[arg(long, value_name = "PATH", requires = "server")]
+    socket: Option<PathBuf>,
+
+    /// When to color matches: auto, always or never. With auto, matches are
+    /// colored when printing to a terminal.
+    #[arg(
+        long,
+        value_name = "WHEN",
+        default_value_t = ColorChoice::Auto,
+        help_heading = help::OUTPUT
+    )]
+    color: ColorChoice,
+
+    /// Color only capture group N of each match rather than the whole match.
+    /// May be given more than once.
+    #[arg(long, value_name = "N", help_heading = help::OUTPUT)]
+    highlight_group: Vec<usize>,
+
+    /// Print matching lines with every match replaced by TEMPLATE, in which
+    /// $N and ${name} refer to capture groups, ${N:-default} supplies a
+    /// default for an empty group, \U, \L and \E change the case of what
+    /// follows and \\ is a backslash.
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        value_parser = Template::parse,
+        help_heading = help::OUTPUT
+    )]
+    replace: Option<Template>,
+
+    /// With --replace, print a unified diff of the replacements in each file,
+    /// with three lines of context unless -C, -A or -B says otherwise,
+    /// rather than the replaced lines. No file is modified, as it is with
+    /// --write.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "json", "hexdump", "files_with_matches", "quiet", "count", "only_matching",
+            "output_format", "histogram", "group_by", "select", "unique", "print0"
+        ],
+        help_heading = help::OUTPUT
+    )]
+    diff: bool,
+
+    /// With --replace, make the replacements in the files themselves rather
+    /// than printing the replaced lines. Only local files can be edited, and
+    /// not files with other hard links; symlinks are followed.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "json", "hexdump", "files_with_matches", "quiet", "count", "only_matching",
+            "output_format", "histogram", "group_by", "select", "unique", "print0", "diff",
+            "follow_lines", "json_input", "csv", "byte_range"
+        ],
+        help_heading = help::OUTPUT
+    )]
+    write: bool,
+
+    /// With --write, keep the original of each edited file next to it, its
+    /// name followed by SUFFIX, or in --backup-dir when given.
+    #[arg(long, value_name = "SUFFIX", requires = "write", help_heading = help::OUTPUT)]
+    backup_suffix: Option<String>,
+
+    /// With --write, keep the original of each edited file below DIR, at
+    /// the path of the file.
+    #[arg(long, value_name = "DIR", requires = "write", help_heading = help::OUTPUT)]
+    backup_dir: Option<PathBuf>,
+
+    /// Print only the matched parts of matching lines, each on its own line.
+    #[arg(short = 'o', long, help_heading = help::OUTPUT)]
+    only_matching: bool,
+
+    /// With --only-matching, print only the capture group GROUP, given by
+    /// number or name, of each match.
+    #[arg(long, value_name = "GROUP", requires = "only_matching", help_heading = help::OUTPUT)]
+    group: Option<String>,
+
+    /// Print each distinct matching line, or match with --only-matching,
+    /// only the first time it is found in any file.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "after_context", "before_context", "context", "function_context", "show_heading",
+            "json", "hexdump", "files_with_matches", "quiet", "count", "output_format", "histogram"
+        ],
+        help_heading = help::OUTPUT
+    )]
+    unique: bool,
+
+    /// Treat warnings about the pattern, such as matching every line, as
+    /// errors.
+    #[arg(long, help_heading = help::MATCHING)]
+    strict: bool,
+
+    /// Print each match as a hex dump of the bytes around it, for searching
+    /// binary files. The context options count rows of 16 bytes, and the
+    /// pattern is matched against raw bytes rather than Unicode text.
+    #[arg(
+        long,
+        conflicts_with_all = ["json", "only_matching", "replace"],
+        help_heading = help::OUTPUT
+    )]
+    hexdump: bool,
+
+    /// Decode files from ENCODING, such as `utf-16le` or `latin1`, or detect
+    /// the encoding of each file with `auto`. Without it, files must be UTF-8.
+    #[arg(
+        long,
+        value_name = "ENCODING",
+        value_parser = Encoding::parse,
+        help_heading = help::MATCHING
+    )]
+    encoding: Option<Encoding>,
+
+    /// Print only the names of files containing a match. The search of each
+    /// file stops at its first match.
+    #[arg(short = 'l', long, conflicts_with_all = ["json", "hexdump"], help_heading = help::OUTPUT)]
+    files_with_matches: bool,
+
+    /// Print only the number of matching lines of each file containing a
+    /// match, after its name when several files are searched.
+    #[arg(
+        short = 'c',
+        long,
+        conflicts_with_all = ["json", "hexdump", "files_with_matches", "quiet"],
+        help_heading = help::OUTPUT
+    )]
+    count: bool,
+
+    /// With --count, also print the files without any match, with a count of
+    /// 0.
+    #[arg(long, requires = "count", help_heading = help::OUTPUT)]
+    include_zero: bool,
+
+    /// Print nothing, and exit with status 0 as soon as any file matches,
+    /// even if others could not be read, or with status 1 when none does.
+    #[arg(short = 'q', long, conflicts_with_all = ["json", "hexdump"], help_heading = help::OUTPUT)]
+    quiet: bool,
+
+    /// Suppress the messages about files that cannot be read. They still make
+    /// the exit status 2.
+    #[arg(short = 's', long, help_heading = help::OUTPUT)]
+    no_messages: bool,
+
+    /// Append the messages about files that cannot be read or decoded, and
+    /// about skipped lines, to the file at PATH instead of printing them.
+    /// They are logged even with --no-messages.
+    #[arg(long, value_name = "PATH", help_heading = help::OUTPUT)]
+    error_log: Option<String>,
+
+    /// Stop searching each file after NUM matching lines. The file is read no
+    /// further than the last of them and its after context.
+    #[arg(short = 'm', long, value_name = "NUM", help_heading = help::MATCHING)]
+    max_count: Option<usize>,
+
+    /// Stop the whole search after the first matching line, printed along
+    /// with its context, rather than searching the remaining files.
+    #[arg(
+        short = '1',
+        long,
+        conflicts_with_all = [
+            "max_count", "function_context", "show_heading", "record_separator", "paragraph",
+            "json_input", "csv", "expr", "all_match", "since", "until"
+        ],
+        help_heading = help::MATCHING
+    )]
+    stop_after_first: bool,
+
+    /// Once every file has been searched, keep following them like `tail
+    /// -f`, printing the matching lines appended to them until interrupted.
+    /// Truncated files are read again from their start, and so are rotated
+    /// ones, once their path names a new file.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "output", "json", "hexdump", "hex", "files_with_matches", "quiet", "count",
+            "output_format", "histogram", "group_by", "path_match", "record_separator",
+            "paragraph", "json_input", "csv", "max_total", "stop_after_first", "global_timeout",
+            "max_count", "line_range", "byte_range"
+        ],
+        help_heading = help::OUTPUT
+    )]
+    follow_lines: bool,
+
+    /// Record the files searched in FILE as the search goes, so that an
+    /// interrupted search can be resumed with --resume.
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["output", "follow_lines"],
+        help_heading = help::PERFORMANCE
+    )]
+    checkpoint: Option<String>,
+
+    /// Skip the files recorded in FILE by --checkpoint, resuming the search
+    /// that wrote it. Usually given the same FILE as --checkpoint.
+    #[arg(long, value_name = "FILE", help_heading = help::PERFORMANCE)]
+    resume: Option<String>,
+
+    /// Give up searching a file after DURATION, such as 30s or 500ms, and
+    /// report it as an error before searching the next one.
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = time::parse_duration,
+        help_heading = help::PERFORMANCE
+    )]
+    timeout: Option<Duration>,
+
+    /// Give up the whole search after DURATION, reporting the file being
+    /// searched as an error and leaving the remaining files unsearched.
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = time::parse_duration,
+        help_heading = help::PERFORMANCE
+    )]
+    global_timeout: Option<Duration>,
+
+    /// The time at which the search gives up, with --global-timeout.
+    #[arg(skip)]
+    deadline: Option<Instant>,
+
+    /// Stop the whole search once NUM matching lines have been printed across
+    /// all files, rather than searching the remaining files.
+    #[arg(
+        long,
+        value_name = "NUM",
+        conflicts_with_all = [
+            "stop_after_first", "function_context", "show_heading", "record_separator",
+            "paragraph", "json_input", "csv", "expr", "all_match", "since", "until"
+        ],
+        help_heading = help::MATCHING
+    )]
+    max_total: Option<usize>,
+
+    /// Print as in a terminal even when not printing to one: color the
+    /// matches, number the lines and print the name of each file above its
+    /// lines, unless --color, --no-line-number or --no-heading say otherwise.
+    #[arg(short = 'p', long, conflicts_with = "json", help_heading = help::OUTPUT)]
+    pretty: bool,
+
+    /// End every printed line, file name and match with a NUL byte rather
+    /// than a newline, for use with `xargs -0` and the like.
+    #[arg(
+        short = '0',
+        long,
+        conflicts_with_all = ["json", "hexdump", "pretty", "heading"],
+        help_heading = help::OUTPUT
+    )]
+    print0: bool,
+
+    /// Print the results as FORMAT: plain text, a SARIF log for code scanning
+    /// services, GitHub Actions annotations, a JUnit XML report, a standalone
+    /// HTML page, a Markdown summary or the output of grep in Emacs.
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        default_value = "text",
+        conflicts_with_all = ["json", "hexdump", "files_with_matches", "quiet", "count", "pretty", "print0"],
+        help_heading = help::OUTPUT
+    )]
+    output_format: OutputFormat,
+
+    /// Rather than the matching lines, print how many matches each distinct
+    /// value of the capture group GROUP, given by number or name, takes,
+    /// the most frequent first.
+    #[arg(
+        long,
+        value_name = "GROUP",
+        conflicts_with_all = [
+            "json", "hexdump", "files_with_matches", "quiet", "count", "only_matching",
+            "output_format", "pretty", "print0"
+        ],
+        help_heading = help::OUTPUT
+    )]
+    histogram: Option<String>,
+
+    /// Rather than the matching lines, print the distinct values the capture
+    /// group GROUP, given by number or name, takes, each with its number of
+    /// matches, the largest groups first.
+    #[arg(
+        long,
+        value_name = "GROUP",
+        conflicts_with_all = [
+            "json", "hexdump", "files_with_matches", "quiet", "count", "only_matching",
+            "output_format", "pretty", "print0", "histogram", "unique"
+        ],
+        help_heading = help::OUTPUT
+    )]
+    group_by: Option<String>,
+
+    /// With --group-by, also print the first and last line matching in each
+    /// group.
+    #[arg(long, requires = "group_by", help_heading = help::OUTPUT)]
+    group_lines: bool,
+
+    /// Rather than printing the matching lines, pick among them on the
+    /// terminal, typing to filter them, and print the chosen ones as
+    /// file:line.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "json", "hexdump", "files_with_matches", "quiet", "count", "output_format",
+            "histogram", "group_by", "pretty", "print0", "path_match", "follow_lines"
+        ],
+        help_heading = help::OUTPUT
+    )]
+    select: bool,
+
+    /// Write the results to FILE rather than to stdout. FILE is only replaced
+    /// once the search is complete, so it never holds partial results.
+    #[arg(long, value_name = "FILE", help_heading = help::OUTPUT)]
+    output: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct ReplaceArgs {
+    /// Replace every match by TEMPLATE, in which $N and ${name} refer to
+    /// capture groups, ${N:-default} supplies a default for an empty group,
+    /// \U, \L and \E change the case of what follows and \\ is a backslash.
+    #[arg(value_name = "TEMPLATE", value_parser = Template::parse)]
+    template: Template,
+
+    #[command(flatten)]
+    search: Args,
+}
+
+#[derive(clap::Args)]
+struct FilesArgs {
+    /// FILE or directory to list. May be given more than once.
+    #[arg(short, long, value_name = "FILE")]
+    file: Vec<String>,
+
+    /// What to do with directories among the files: list them, list every
+    /// file below them, or skip them.
+    #[arg(
+        short = 'd',
+        long,
+        value_name = "ACTION",
+        value_enum,
+        default_value_t = DirectoryAction::Recurse
+    )]
+    directories: DirectoryAction,
+
+    /// What to do with FIFOs, sockets and device nodes among the files: list
+    /// them or skip them.
+    #[arg(
+        short = 'D',
+        long,
+        value_name = "ACTION",
+        value_enum,
+        default_value_t = DeviceAction::Read
+    )]
+    devices: DeviceAction,
+
+    #[command(flatten)]
+    ignore: IgnoreOptions,
+
+    /// Only list files modified after TIME, given either as a duration
+    /// before now (e.g. 2d, 90m) or as a date (YYYY-MM-DD).
+    #[arg(long, value_name = "TIME", value_parser = time::parse_time)]
+    newer_than: Option<SystemTime>,
+
+    /// Only list files modified before TIME, given either as a duration
+    /// before now (e.g. 2d, 90m) or as a date (YYYY-MM-DD).
+    #[arg(long, value_name = "TIME", value_parser = time::parse_time)]
+    older_than: Option<SystemTime>,
+
+    /// End every file name with a NUL byte rather than a newline.
+    #[arg(short = '0', long)]
+    print0: bool,
+
+    /// Suppress the messages about directories that cannot be read.
+    #[arg(short = 's', long)]
+    no_messages: bool,
+}
+
+/// Statistics aggregated over every file searched.
+struct Totals {
+    stats: Stats,
+    files_searched: usize,
+    files_matched: usize,
+    /// Files that could not be read in full, along with the error.
+    files_failed: Vec<(String, io::Error)>,
+    /// Whether the last line printed was left without a terminator, as it
+    /// was in its file.
+    unterminated: bool,
+    /// Each file containing a match with its statistics, for
+    /// --count-summary.
+    files: Vec<(String, Stats)>,
+    /// The lines printed so far, for --unique.
+    printed: HashSet<String>,
+    /// Whether the search gave up at --global-timeout before every file was
+    /// searched.
+    timed_out: bool,
+    /// Where the files searched are recorded, with --checkpoint.
+    checkpoint: Option<Checkpoint>,
+    /// The statistics of the files searched by each worker thread.
+    workers: Vec<Stats>,
+    /// Where the search of each file stopped, from which --follow-lines
+    /// carries on.
+    positions: HashMap<String, (usize, usize)>,
+}
+
+/// Parses a range of the form `START..END`, where both bounds are inclusive
+/// and either may be omitted (`START..`, `..END`). The other forms accepted by
+/// `Interval`, `[START,END]` and a single number, are accepted as well.
+fn parse_range(s: &str) -> Result<RangeInclusive<usize>, String> {
+    let s = match s.split_once("..") {
+        Some((start, end)) if start.is_empty() || end.is_empty() => {
+            let start = if start.is_empty() { "0" } else { start };
+            let end = match end {
+                "" => usize::MAX.to_string(),
+                end => end.to_string(),
+            };
+            format!("{start}..{end}")
+        }
+        _ => s.to_string(),
+    };
+
+    s.parse::<Interval<usize>>().map(RangeInclusive::from)
+}
+
+/// Parses a size in bytes with an optional binary K, M or G suffix.
+fn parse_size(s: &str) -> Result<usize, String> {
+    let (digits, multiplier) = match s.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&s[..i], 1 << 10),
+        Some((i, 'M' | 'm')) => (&s[..i], 1 << 20),
+        Some((i, 'G' | 'g')) => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+
+    let size: usize = digits.parse().map_err(|e| format!("invalid size: {e}"))?;
+    size.checked_mul(multiplier)
+        .ok_or_else(|| format!("size '{s}' is too large"))
 }
 
 fn main() {
-    let args = Args::parse();
-    let after_context = args.after_context.unwrap_or_default();
-    let before_context = args.before_context.unwrap_or_default();
-    let pattern = args.pattern.unwrap_or_default();
-    let file = match args.file {
-        Some(file) => file,
-        None => panic!("File name must be provided"),
+    match Cli::parse_from(config::args()).into_search() {
+        Ok(args) => run_search(args),
+        Err(Command::Files(files)) => list_files(&files),
+        Err(Command::Types) => list_types(),
+        Err(Command::Search(_) | Command::Replace(_)) => unreachable!(),
+    }
+}
+
+/// Prints the files that `search` would search for the `files` options.
+fn list_files(files: &FilesArgs) {
+    let paths = match files.file.is_empty() {
+        true => vec![".".to_string()],
+        false => files.file.clone(),
+    };
+    let (paths, errors) =
+        walk::expand(&paths, files.directories, files.devices, files.ignore, None);
+    for (dir, e) in &errors {
+        messages::report(&format!("{dir}: {e}"), files.no_messages);
+    }
+
+    let terminator = if files.print0 { '\0' } else { '\n' };
+    let mut out = BufWriter::new(io::stdout().lock());
+    let mut failed = !errors.is_empty();
+    for path in &paths {
+        match is_modified_within(path, files.newer_than, files.older_than) {
+            Ok(true) => {
+                if let Err(e) = write!(out, "{path}{terminator}") {
+                    panic!("{e}");
+                }
+            }
+            Ok(false) => {}
+            Err(e) => {
+                messages::report(&format!("{path}: {e}"), files.no_messages);
+                failed = true;
+            }
+        }
+    }
+    if let Err(e) = out.flush() {
+        panic!("{e}");
+    }
+    if failed {
+        process::exit(2);
+    }
+}
+
+/// Prints the document types that can be decoded, with the extensions that
+/// identify them and whether they are enabled in this build.
+fn list_types() {
+    for (name, extensions, enabled) in decoder::TYPES {
+        let extensions: Vec<String> = extensions.iter().map(|e| format!("*.{e}")).collect();
+        let status = match enabled {
+            true => String::new(),
+            false => format!(" (requires the {name} feature)"),
+        };
+        println!("{name}: {}{status}", extensions.join(", "));
+    }
+}
+
+/// Searches the files given in `args`, or serves search requests with
+/// --server, and exits with the status of the search.
+fn run_search(mut args: Args) {
+    if args.server {
+        if let Err(e) = server::run(args.socket.as_deref()) {
+            panic!("{e}");
+        }
+        return;
+    }
+
+    // Stdin cannot hold both the patterns and the text to search.
+    let searches_stdin = match args.file.is_empty() {
+        true => !args.path_match,
+        false => args.file.iter().any(|file| file == STDIN),
     };
+    if args.pattern_file.as_deref() == Some(STDIN) && searches_stdin {
+        eprintln!("error: --pattern-file - reads the patterns from stdin, so a file to search must be given");
+        process::exit(2);
+    }
+
+    let (pattern, patterns) = match read_pattern(&args) {
+        Ok(pattern) => pattern,
+        Err(e) => panic!("{e}"),
+    };
+    if args.file.is_empty() {
+        let file = if args.path_match { "." } else { STDIN };
+        args.file.push(file.to_string());
+    }
+    if let Some(path) = &args.error_log {
+        if let Err(e) = messages::open_log(path) {
+            panic!("{path}: {e}");
+        }
+    }
+    if let Err(e) = settle(&mut args) {
+        panic!("{e}");
+    }
+    // Only a local file can be replaced by its edited copy.
+    if let Some(file) = args.file.iter().find(|file| args.write && !is_local(file)) {
+        let file = if file == STDIN { "stdin" } else { file };
+        messages::report(
+            &format!("error: --write cannot edit {file}, only local files"),
+            args.no_messages,
+        );
+        process::exit(2);
+    }
+    let start = Instant::now();
+    let walk_errors = expand_files(&mut args, None);
+    let walk_time = start.elapsed();
+    for (dir, e) in &walk_errors {
+        messages::report(&format!("{dir}: {e}"), args.no_messages);
+    }
 
     let re = match Regex::new(pattern.as_str()) {
         Ok(re) => re,
-        Err(e) => panic!("{e}"),
+        Err(e) => {
+            eprint!("{}", diagnostic::explain(&pattern, &e));
+            process::exit(2);
+        }
     };
 
-    let f = match File::open(file.as_str()) {
-        Ok(f) => f,
-        Err(e) => panic!("{e}"),
+    if let Err(e) = compile_patterns(&mut args, patterns) {
+        panic!("{e}");
+    }
+
+    if matches_every_line(&re) && args.expr.is_none() {
+        let severity = if args.strict { "error" } else { "warning" };
+        eprintln!(
+            "{severity}: the pattern '{pattern}' matches the empty string, so every line will match"
+        );
+        if args.strict {
+            process::exit(2);
+        }
+    }
+
+    if let Err(e) = check_groups(&re, &args) {
+        eprintln!("error: {e}");
+        process::exit(2);
+    }
+
+    let stdout = io::stdout();
+    let sink: Box<dyn Write> = match &args.output {
+        Some(path) => match File::create(partial_path(path)) {
+            Ok(f) => Box::new(f),
+            Err(e) => panic!("{}: {e}", path.display()),
+        },
+        None => Box::new(stdout.lock()),
     };
+    let mut out = BufWriter::with_capacity(args.max_buffer, sink);
 
-    let lines: Vec<String> = BufReader::new(f).lines().map(|r| r.unwrap()).collect();
-    for (i, line) in lines.iter().enumerate() {
-        if !re.is_match(line.as_str()) {
-            continue;
+    let Totals {
+        stats: total,
+        files_searched,
+        files_matched,
+        files_failed,
+        mut files,
+        timed_out,
+        workers,
+        positions,
+        unterminated,
+        ..
+    } = search(&mut out, &re, &args);
+
+    if let Err(e) = out.flush() {
+        panic!("{e}");
+    }
+    if let Some(path) = &args.output {
+        if let Err(e) = fs::rename(partial_path(path), path) {
+            panic!("{}: {e}", path.display());
         }
+    }
+    if args.follow_lines {
+        follow(&mut out, &re, &args, &positions, unterminated);
+    }
 
-        let lower_bound = i.saturating_sub(before_context);
-        let upper_bound = (lines.len() - 1).min(i + after_context);
+    if args.summary {
+        eprintln!(
+            "{} matches across {files_matched} files ({files_searched} files searched)",
+            total.matches
+        );
+    }
 
-        for (i, line) in (lower_bound + 1..=upper_bound + 1)
-            .zip(lines.iter().take(upper_bound + 1).skip(lower_bound))
-        {
-            println!("{i}: {line}");
+    if let Some(format) = args.stats {
+        let summary = stats::Summary {
+            total,
+            workers: &workers,
+            walk_time,
+            files_searched,
+            files_matched,
+        };
+        match format {
+            stats::Format::Table => eprint!("{}", summary.table()),
+            stats::Format::Json => eprintln!("{}", summary.json()),
+        }
+    }
+
+    if args.count_summary {
+        files.sort_by(|(a, a_stats), (b, b_stats)| {
+            b_stats.matches.cmp(&a_stats.matches).then_with(|| a.cmp(b))
+        });
+        eprint!("{}", stats::count_table(&files, total));
+    }
+
+    if timed_out {
+        messages::report("gave up the search at --global-timeout", args.no_messages);
+    }
+
+    // With --quiet, as with grep -q, a match settles the exit status
+    // whatever files could not be read.
+    if args.quiet && files_matched > 0 {
+        return;
+    }
+
+    // Results from a file that could not be read in full are incomplete, so
+    // they cannot settle the exit status either way.
+    if !files_failed.is_empty() || !walk_errors.is_empty() || timed_out {
+        process::exit(2);
+    }
+
+    if let Some(required) = args.require_count {
+        if total.matches < required {
+            eprintln!(
+                "found {} matches, at least {required} required",
+                total.matches
+            );
+            process::exit(1);
+        }
+    }
+
+    if args.quiet && files_matched == 0 {
+        process::exit(1);
+    }
+}
+
+/// Checks that the capture groups named or numbered by the options of `args`
+/// exist in `re`.
+fn check_groups(re: &Regex, args: &Args) -> Result<(), String> {
+    let numbers = args.highlight_group.iter().map(usize::to_string);
+    let groups = args
+        .group
+        .iter()
+        .chain(&args.histogram)
+        .chain(&args.group_by)
+        .cloned();
+    for group in numbers.chain(groups) {
+        let exists = match group.parse::<usize>() {
+            Ok(group) => group < re.captures_len(),
+            Err(_) => re.capture_names().any(|name| name == Some(group.as_str())),
+        };
+        if !exists {
+            return Err(format!("the pattern has no capture group {group}"));
+        }
+    }
+    Ok(())
+}
+
+/// Settles the options of `args` that follow from others, before its files
+/// are expanded and searched.
+fn settle(args: &mut Args) -> Result<(), String> {
+    // WebAssembly has no threads to search with.
+    if cfg!(target_family = "wasm") {
+        args.threads = 1;
+    }
+    if args.recursive || (args.path_match && args.directories == DirectoryAction::Read) {
+        args.directories = DirectoryAction::Recurse;
+    }
+    args.hexdump |= args.hex.is_some();
+    if (args.diff || args.write) && args.replace.is_none() {
+        let option = if args.diff { "--diff" } else { "--write" };
+        return Err(format!(
+            "{option} requires a replacement, given with --replace or by the replace command"
+        ));
+    }
+    if args.diff {
+        args.context.get_or_insert(3);
+    }
+    if args.stop_after_first {
+        args.max_count = Some(1);
+    }
+    args.deadline = args.global_timeout.map(|timeout| Instant::now() + timeout);
+    // No file needs to be searched past the matches that can still be
+    // printed.
+    if let Some(max) = args.max_total {
+        args.max_count = Some(args.max_count.map_or(max, |count| count.min(max)));
+    }
+    resolve_output(args);
+    Ok(())
+}
+
+/// Replaces the files given in `args` by the objects under each S3 prefix and
+/// the files below each directory, leaving out those already searched with
+/// --resume. Ignore files are looked up in `ignores` before being read.
+/// Returns the prefixes and directories that could not be read.
+fn expand_files(
+    args: &mut Args,
+    ignores: Option<&mut walk::IgnoreCache>,
+) -> Vec<(String, io::Error)> {
+    let list_errors = list_objects(args);
+    let mut walk_errors;
+    (args.file, walk_errors) = walk::expand(
+        &args.file,
+        args.directories,
+        args.devices,
+        args.ignore,
+        ignores,
+    );
+    // Prefixes that cannot be listed fail like directories that cannot be
+    // read.
+    walk_errors.extend(list_errors);
+    if let Some(path) = &args.resume {
+        match checkpoint::completed(path) {
+            Ok(completed) => args.file.retain(|file| !completed.contains(file)),
+            Err(e) => panic!("{path}: {e}"),
+        }
+    }
+    walk_errors
+}
+
+/// How long to wait between polls of the files followed with --follow-lines.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Follows the files given in `args` with --follow-lines, printing the lines
+/// matching `re` as they are appended, until the process is interrupted.
+/// Each file is followed from the line and offset in `positions` its search
+/// stopped at, and those that were not searched are left out. `unterminated`
+/// tells whether the search left the last line printed without a terminator.
+fn follow(
+    out: &mut impl Write,
+    re: &Regex,
+    args: &Args,
+    positions: &HashMap<String, (usize, usize)>,
+    mut unterminated: bool,
+) -> ! {
+    let mut followers: Vec<Follower> = args
+        .file
+        .iter()
+        .filter(|&path| is_local(path))
+        .filter_map(|path| Some((path, *positions.get(path)?)))
+        .filter_map(
+            |(path, (number, offset))| match Follower::new(path, number, offset) {
+                Ok(follower) => Some(follower),
+                Err(e) => {
+                    messages::report(&format!("{path}: {e}"), args.no_messages);
+                    None
+                }
+            },
+        )
+        .collect();
+
+    let mut printed = HashSet::new();
+    loop {
+        for follower in &mut followers {
+            let lines = match follower.poll() {
+                Ok(lines) => lines,
+                Err(e) => {
+                    messages::report(&format!("{}: {e}", follower.path()), args.no_messages);
+                    continue;
+                }
+            };
+            let lines: Vec<Line> = lines
+                .into_iter()
+                .filter(|(_, _, text)| re.is_match(text))
+                .map(|(number, offset, text)| Line {
+                    number,
+                    offset,
+                    text: Cow::Owned(text),
+                    matched: true,
+                    terminated: true,
+                    header: false,
+                })
+                .collect();
+            if lines.is_empty() {
+                continue;
+            }
+            let stats = Stats {
+                matched_lines: lines.len(),
+                matches: lines
+                    .iter()
+                    .map(|line| re.find_iter(&line.text).count())
+                    .sum(),
+                ..Default::default()
+            };
+            print_lines(
+                out,
+                follower.path(),
+                re,
+                args,
+                lines,
+                stats,
+                true,
+                true,
+                &mut unterminated,
+                &mut printed,
+                None,
+            );
+        }
+        if let Err(e) = out.flush() {
+            panic!("{e}");
+        }
+        thread::sleep(FOLLOW_INTERVAL);
+    }
+}
+
+/// Returns the path of the temporary file the results are written to before
+/// replacing `path`. It is in the same directory, so that renaming it over
+/// `path` is atomic.
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".{}.tmp", process::id()));
+    path.with_file_name(name)
+}
+
+/// Returns the patterns given with --pattern or after `--`, combined with
+/// those read from --pattern-file into a single alternation, along with each
+/// pattern on its own. All of them are made case-insensitive as requested by
+/// the case options.
+fn read_pattern(args: &Args) -> Result<(String, Vec<String>), String> {
+    let mut patterns = read_patterns(args)?;
+    let mut pattern = match patterns.as_slice() {
+        [] => String::new(),
+        [pattern] => pattern.clone(),
+        patterns => {
+            let patterns: Vec<String> = patterns.iter().map(|p| format!("(?:{p})")).collect();
+            patterns.join("|")
+        }
+    };
+    // An expression holding for lines that match none of its patterns, such
+    // as `NOT foo`, needs every line searched.
+    if args.expr.as_ref().is_some_and(|expr| expr.eval(|_| false)) {
+        pattern.push_str("|^");
+    }
+
+    let ignore_case = args.ignore_case || (args.smart_case && !has_uppercase(&pattern));
+    if !ignore_case {
+        return Ok((pattern, patterns));
+    }
+    for p in &mut patterns {
+        p.insert_str(0, "(?i)");
+    }
+    Ok((format!("(?i){pattern}"), patterns))
+}
+
+/// Compiles each of `patterns` on its own for --expr, which --all-match is
+/// turned into.
+fn compile_patterns(args: &mut Args, patterns: Vec<String>) -> Result<(), String> {
+    if let Some(scope) = args.all_match {
+        args.expr = Some(Expr::all(patterns.clone()));
+        args.expr_scope = scope;
+    }
+    if args.expr.is_some() {
+        args.patterns = patterns
+            .iter()
+            .map(|pattern| Regex::new(pattern).map_err(|e| e.to_string()))
+            .collect::<Result<_, _>>()?;
+    }
+    Ok(())
+}
+
+/// Returns whether `pattern` contains an uppercase letter to be matched
+/// literally, as opposed to one in an escape such as `\S`. Patterns that
+/// fail to parse are treated as containing one.
+fn has_uppercase(pattern: &str) -> bool {
+    use regex_syntax::ast::{self, Ast, ClassSetItem};
+
+    struct Uppercase(bool);
+
+    impl ast::Visitor for Uppercase {
+        type Output = bool;
+        type Err = ();
+
+        fn finish(self) -> Result<bool, ()> {
+            Ok(self.0)
+        }
+
+        fn visit_pre(&mut self, ast: &Ast) -> Result<(), ()> {
+            if let Ast::Literal(literal) = ast {
+                self.0 |= literal.c.is_uppercase();
+            }
+            Ok(())
+        }
+
+        fn visit_class_set_item_pre(&mut self, item: &ClassSetItem) -> Result<(), ()> {
+            match item {
+                ClassSetItem::Literal(literal) => self.0 |= literal.c.is_uppercase(),
+                ClassSetItem::Range(range) => {
+                    self.0 |= range.start.c.is_uppercase() || range.end.c.is_uppercase();
+                }
+                _ => {}
+            }
+            Ok(())
+        }
+    }
+
+    match ast::parse::Parser::new().parse(pattern) {
+        Ok(ast) => ast::visit(&ast, Uppercase(false)).unwrap_or(true),
+        Err(_) => true,
+    }
+}
+
+/// Returns the patterns given with --pattern or after `--`, followed by
+/// those read from --pattern-file, or those of --expr, or the pattern of
+/// --hex.
+fn read_patterns(args: &Args) -> Result<Vec<String>, String> {
+    if let Some(expr) = &args.expr {
+        return Ok(expr.patterns().to_vec());
+    }
+    if let Some(hex) = &args.hex {
+        return Ok(vec![hex.clone()]);
+    }
+    let mut patterns: Vec<String> = args
+        .pattern
+        .iter()
+        .chain(&args.trailing_pattern)
+        .cloned()
+        .collect();
+    let Some(path) = &args.pattern_file else {
+        return Ok(patterns);
+    };
+
+    let contents = match path.as_str() {
+        "-" => io::read_to_string(io::stdin()),
+        path => fs::read_to_string(path),
+    }
+    .map_err(|e| format!("{path}: {e}"))?;
+    patterns.extend(contents.lines().map(String::from));
+    Ok(patterns)
+}
+
+/// Returns whether `re` matches any line, which is the case when it matches
+/// both an empty line and a line that is unlikely to appear in its pattern.
+/// Patterns such as `^$` only match the former.
+fn matches_every_line(re: &Regex) -> bool {
+    re.is_match("") && re.is_match("\u{0}")
+}
+
+/// Searches every file given in `args` for `re`, printing the results to
+/// `out`. With more than one thread and more than one file, the files are
+/// searched by a pool of workers, each taking the next file from a shared
+/// queue, while the results are printed in the order the files were given.
+fn search(out: &mut impl Write, re: &Regex, args: &Args) -> Totals {
+    let mut totals = Totals {
+        stats: Stats::default(),
+        files_searched: 0,
+        files_matched: 0,
+        files_failed: Vec::new(),
+        unterminated: false,
+        files: Vec::new(),
+        printed: HashSet::new(),
+        timed_out: false,
+        checkpoint: args
+            .checkpoint
+            .as_ref()
+            .map(|path| match Checkpoint::open(path) {
+                Ok(checkpoint) => checkpoint,
+                Err(e) => panic!("{path}: {e}"),
+            }),
+        workers: Vec::new(),
+        positions: HashMap::new(),
+    };
+
+    let mut report = match (&args.histogram, &args.group_by, args.output_format) {
+        _ if args.select => Some(Report::selection()),
+        (Some(group), _, _) => Some(Report::histogram(group.clone())),
+        (_, Some(group), _) => Some(Report::groups(group.clone(), args.group_lines)),
+        (None, None, OutputFormat::Text) => None,
+        (None, None, format) => match Report::begin(out, format, re) {
+            Ok(report) => Some(report),
+            Err(e) => panic!("{e}"),
+        },
+    };
+
+    // Neither hex dumps nor paths are split into lines, so their files are
+    // handled one at a time below.
+    let lines = !args.hexdump && !args.path_match;
+    // Objects in S3 are downloaded by as many threads as can be in flight,
+    // since the latency of each request dominates.
+    let threads = match args.file.iter().any(|file| s3::is_url(file)) {
+        true => args.threads.max(s3::IN_FLIGHT),
+        false => args.threads,
+    };
+    if args.io == IoMode::Async && lines {
+        search_ahead(out, re, args, &mut totals, report.as_mut());
+    } else if threads > 1 && args.file.len() > 1 && lines {
+        let workers = threads.min(args.file.len());
+        let done = &AtomicBool::new(false);
+
+        thread::scope(|s| {
+            let (tx, rx) = mpsc::channel();
+            s.spawn(move || search_files(re, args, workers, tx, done));
+
+            // Results arriving out of order wait for those of earlier files.
+            let mut pending = BTreeMap::new();
+            let mut next_file = 0;
+            totals.workers = vec![Stats::default(); workers];
+            'results: for (i, worker, outcome) in rx {
+                if let Some(stats) = outcome.stats() {
+                    totals.workers[worker] += stats;
+                }
+                pending.insert(i, outcome);
+                while let Some(outcome) = pending.remove(&next_file) {
+                    let file = &args.file[next_file];
+                    next_file += 1;
+                    if !emit(out, file, outcome, re, args, &mut totals, report.as_mut()) {
+                        done.store(true, Ordering::Relaxed);
+                        break 'results;
+                    }
+                }
+            }
+        });
+    } else {
+        for file in &args.file {
+            let keep_going = if args.hexdump {
+                let outcome = match is_modified_within(file, args.newer_than, args.older_than) {
+                    Ok(true) => match hexdump_file(out, file, re, args) {
+                        Ok(stats) => Outcome::Printed(stats),
+                        Err(e) => Outcome::Failed(e),
+                    },
+                    Ok(false) => Outcome::Skipped,
+                    Err(e) => Outcome::Failed(e),
+                };
+                emit(out, file, outcome, re, args, &mut totals, report.as_mut())
+            } else if streams(file, args) {
+                let separate = totals.files_matched > 0;
+                let outcome = match stream_stdin(out, re, args, separate, &mut totals) {
+                    Ok(stats) => Outcome::Printed(stats),
+                    Err(e) => Outcome::Failed(e),
+                };
+                emit(out, file, outcome, re, args, &mut totals, report.as_mut())
+            } else if args.path_match {
+                let outcome = match is_modified_within(file, args.newer_than, args.older_than) {
+                    Ok(true) => Outcome::First(match_path(file, re)),
+                    Ok(false) => Outcome::Skipped,
+                    Err(e) => Outcome::Failed(e),
+                };
+                emit(out, file, outcome, re, args, &mut totals, report.as_mut())
+            } else {
+                search_one(file, None, re, args, args.threads, |outcome| {
+                    emit(out, file, outcome, re, args, &mut totals, report.as_mut())
+                })
+            };
+            if !keep_going {
+                break;
+            }
         }
     }
+
+    // Every file was searched on this thread.
+    if totals.workers.is_empty() {
+        totals.workers.push(Stats {
+            print_time: Duration::ZERO,
+            ..totals.stats
+        });
+    }
+
+    if let Some(report) = report {
+        match report.end(out) {
+            Ok(()) => {}
+            // The picker of --select was closed without choosing.
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => process::exit(130),
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    if let Some(checkpoint) = &mut totals.checkpoint {
+        if let Err(e) = checkpoint.record(out) {
+            panic!("{e}");
+        }
+    }
+
+    if args.json {
+        let result = json::summary(
+            out,
+            totals.files_searched,
+            totals.files_matched,
+            totals.stats,
+        );
+        if let Err(e) = result {
+            panic!("{e}");
+        }
+    }
+
+    totals
+}
+
+/// The result of searching a file, before it is printed.
+enum Outcome<'a> {
+    /// The file was skipped without being searched.
+    Skipped,
+    /// The file was searched for its first match only, with -l or -q.
+    First(Stats),
+    /// The lines selected for printing.
+    Lines(Vec<Line<'a>>, Stats),
+    /// The results have already been printed.
+    Printed(Stats),
+    /// The file could not be read in full, so its partial results are
+    /// dropped.
+    Failed(io::Error),
+}
+
+impl Outcome<'_> {
+    fn into_owned(self) -> Outcome<'static> {
+        match self {
+            Outcome::Skipped => Outcome::Skipped,
+            Outcome::First(stats) => Outcome::First(stats),
+            Outcome::Lines(lines, stats) => {
+                Outcome::Lines(lines.into_iter().map(Line::into_owned).collect(), stats)
+            }
+            Outcome::Printed(stats) => Outcome::Printed(stats),
+            Outcome::Failed(e) => Outcome::Failed(e),
+        }
+    }
+
+    /// Returns the statistics of the search, unless the file was skipped or
+    /// could not be read.
+    fn stats(&self) -> Option<Stats> {
+        match self {
+            Outcome::First(stats) | Outcome::Lines(_, stats) | Outcome::Printed(stats) => {
+                Some(*stats)
+            }
+            Outcome::Skipped | Outcome::Failed(_) => None,
+        }
+    }
+}
+
+/// Searches every file on a pool of `workers` threads, sending the outcome of
+/// each to `tx` along with its index and that of the worker. Files are taken
+/// from a shared queue until it is empty or `done` is set.
+#[cfg(not(feature = "rayon"))]
+fn search_files(
+    re: &Regex,
+    args: &Args,
+    workers: usize,
+    tx: mpsc::Sender<(usize, usize, Outcome<'static>)>,
+    done: &AtomicBool,
+) {
+    let next = &AtomicUsize::new(0);
+    thread::scope(|s| {
+        for worker in 0..workers {
+            let tx = tx.clone();
+            s.spawn(move || {
+                while !done.load(Ordering::Relaxed) {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(file) = args.file.get(i) else {
+                        break;
+                    };
+                    // The pool already keeps every thread busy, so files are
+                    // not split into chunks.
+                    let outcome =
+                        search_one(file, None, re, args, 1, |outcome| outcome.into_owned());
+                    if tx.send((i, worker, outcome)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+}
+
+/// Searches every file on a rayon pool of `workers` threads, sending the
+/// outcome of each to `tx` along with its index and that of the worker.
+/// Files are skipped once `done` is set.
+#[cfg(feature = "rayon")]
+fn search_files(
+    re: &Regex,
+    args: &Args,
+    workers: usize,
+    tx: mpsc::Sender<(usize, usize, Outcome<'static>)>,
+    done: &AtomicBool,
+) {
+    use rayon::prelude::*;
+
+    let pool = match rayon::ThreadPoolBuilder::new().num_threads(workers).build() {
+        Ok(pool) => pool,
+        Err(e) => panic!("{e}"),
+    };
+    pool.install(|| {
+        args.file
+            .par_iter()
+            .enumerate()
+            .for_each_with(tx, |tx, (i, file)| {
+                if done.load(Ordering::Relaxed) {
+                    return;
+                }
+                // Work stealing keeps idle threads busy with the chunks of
+                // large files without oversubscribing the pool.
+                let outcome = search_one(file, None, re, args, workers, |outcome| {
+                    outcome.into_owned()
+                });
+                let worker = rayon::current_thread_index().unwrap_or_default();
+                let _ = tx.send((i, worker, outcome));
+            });
+    });
+}
+
+/// Searches every file in order as its contents arrive from the reads kept
+/// in flight ahead of the search, printing the outcome of each.
+#[cfg(feature = "tokio")]
+fn search_ahead(
+    out: &mut impl Write,
+    re: &Regex,
+    args: &Args,
+    totals: &mut Totals,
+    mut report: Option<&mut Report>,
+) {
+    let done = &AtomicBool::new(false);
+
+    thread::scope(|s| {
+        let (tx, rx) = mpsc::channel();
+        s.spawn(move || prefetch::read_all(&args.file, args.timeout, tx, done));
+
+        // Files read out of order wait for those before them.
+        let mut pending = BTreeMap::new();
+        let mut next_file = 0;
+        'results: for (i, prefetched) in rx {
+            pending.insert(i, prefetched);
+            while let Some(prefetched) = pending.remove(&next_file) {
+                let file = &args.file[next_file];
+                next_file += 1;
+                let keep_going = match prefetched.contents {
+                    Ok(contents) => search_one(file, contents, re, args, args.threads, |outcome| {
+                        emit(out, file, outcome, re, args, totals, report.as_deref_mut())
+                    }),
+                    Err(e) => emit(
+                        out,
+                        file,
+                        Outcome::Failed(e),
+                        re,
+                        args,
+                        totals,
+                        report.as_deref_mut(),
+                    ),
+                };
+                if !keep_going {
+                    done.store(true, Ordering::Relaxed);
+                    break 'results;
+                }
+            }
+        }
+    });
+}
+
+#[cfg(not(feature = "tokio"))]
+fn search_ahead(
+    _out: &mut impl Write,
+    _re: &Regex,
+    _args: &Args,
+    _totals: &mut Totals,
+    _report: Option<&mut Report>,
+) {
+    panic!("--io async requires the tokio feature");
+}
+
+/// Searches `path` for `re`, splitting it into `threads` chunks, and passes
+/// the outcome to `f`. The file is read unless its `contents` were read
+/// ahead of time.
+fn search_one<R>(
+    path: &str,
+    contents: Option<Vec<u8>>,
+    re: &Regex,
+    args: &Args,
+    threads: usize,
+    f: impl FnOnce(Outcome) -> R,
+) -> R {
+    match is_modified_within(path, args.newer_than, args.older_than) {
+        Ok(true) => {}
+        Ok(false) => return f(Outcome::Skipped),
+        Err(e) => return f(Outcome::Failed(e)),
+    }
+    // A first match cannot tell whether the expression holds or the line
+    // falls within the time window, nor how many matches the summary should
+    // count, and a line cannot hold a match spanning its record or tell the
+    // values of a JSON document or the fields of a CSV row apart.
+    let first_only = args.quiet || args.files_with_matches;
+    let windowed = args.since.is_some() || args.until.is_some();
+    let records = args.record_separator.is_some() || args.paragraph || args.json_input || args.csv;
+    if first_only && args.expr.is_none() && !windowed && !records && !args.count_summary {
+        return f(match find_first(path, contents, re, args) {
+            Ok(stats) => Outcome::First(stats),
+            Err(e) => Outcome::Failed(e),
+        });
+    }
+    find_lines(path, contents, re, args, threads, |result| {
+        f(match result {
+            Ok((lines, stats)) => {
+                let (lines, stats) = apply_expr(lines, stats, re, args);
+                let (lines, stats) = apply_window(lines, stats, re, args);
+                match first_only {
+                    true => Outcome::First(stats),
+                    false => Outcome::Lines(lines, stats),
+                }
+            }
+            Err(e) => Outcome::Failed(e),
+        })
+    })
+}
+
+/// Applies --expr to the `lines` selected from a file, which match any of
+/// its patterns. In the `file` scope, nothing is selected unless the
+/// expression holds for the matching lines taken together. In the `line`
+/// scope, only the lines for which it holds remain matches, and only the
+/// context around them remains selected.
+fn apply_expr<'a>(
+    mut lines: Vec<Line<'a>>,
+    mut stats: Stats,
+    re: &Regex,
+    args: &Args,
+) -> (Vec<Line<'a>>, Stats) {
+    let Some(expr) = &args.expr else {
+        return (lines, stats);
+    };
+    match args.expr_scope {
+        Scope::File => {
+            let holds = expr.eval(|i| {
+                lines
+                    .iter()
+                    .any(|line| line.matched && args.patterns[i].is_match(&line.text))
+            });
+            if !holds {
+                lines.clear();
+                stats.matched_lines = 0;
+                stats.matches = 0;
+            }
+        }
+        Scope::Line => {
+            for line in lines.iter_mut().filter(|line| line.matched) {
+                line.matched = expr.eval(|i| args.patterns[i].is_match(&line.text));
+            }
+            retain_context(&mut lines, &mut stats, re, args);
+        }
+    }
+    (lines, stats)
+}
+
+/// Applies --since and --until to the `lines` selected from a file. Only the
+/// matching lines with a timestamp within the window remain matches, and
+/// only the context around them remains selected.
+fn apply_window<'a>(
+    mut lines: Vec<Line<'a>>,
+    mut stats: Stats,
+    re: &Regex,
+    args: &Args,
+) -> (Vec<Line<'a>>, Stats) {
+    if args.since.is_none() && args.until.is_none() {
+        return (lines, stats);
+    }
+    for line in lines.iter_mut().filter(|line| line.matched) {
+        line.matched = time::line_time(&args.timestamp_pattern, &line.text).is_some_and(|t| {
+            args.since.is_none_or(|since| t >= since) && args.until.is_none_or(|until| t <= until)
+        });
+    }
+    retain_context(&mut lines, &mut stats, re, args);
+    (lines, stats)
+}
+
+/// Drops the `lines` outside the context of the lines still matching, once
+/// some of the matches were ruled out, and counts the remaining matches in
+/// `stats`.
+fn retain_context(lines: &mut Vec<Line>, stats: &mut Stats, re: &Regex, args: &Args) {
+    // Every line within the context of the remaining matches was already
+    // selected around the matches ruled out.
+    let after_context = args.after_context.or(args.context).unwrap_or_default();
+    let before_context = args.before_context.or(args.context).unwrap_or_default();
+    let windows: IntervalSet<usize> = lines
+        .iter()
+        .filter(|line| line.matched)
+        .map(|line| {
+            let number = line.number;
+            Interval::new(number, number).expand(before_context, after_context, None)
+        })
+        .collect();
+    lines.retain(|line| windows.contains(line.number));
+
+    let matched = lines.iter().filter(|line| line.matched);
+    stats.matched_lines = matched.clone().count();
+    stats.matches = matched.map(|line| re.find_iter(&line.text).count()).sum();
+}
+
+/// Prints the `outcome` of searching `file` and adds its statistics to
+/// `totals`. Returns whether the remaining files should be searched.
+fn emit(
+    out: &mut impl Write,
+    file: &str,
+    outcome: Outcome,
+    re: &Regex,
+    args: &Args,
+    totals: &mut Totals,
+    report: Option<&mut Report>,
+) -> bool {
+    let outcome = match (outcome, args.max_total) {
+        (Outcome::Lines(mut lines, mut stats), Some(max)) => {
+            let remaining = max.saturating_sub(totals.stats.matched_lines);
+            limit_matches(&mut lines, &mut stats, remaining, re, args);
+            Outcome::Lines(lines, stats)
+        }
+        (outcome, _) => outcome,
+    };
+    let stats = match outcome {
+        Outcome::Skipped => {
+            complete(out, file, totals);
+            return true;
+        }
+        Outcome::First(stats) => {
+            if stats.matched_lines > 0 {
+                // A single match settles the exit status, so the remaining
+                // files are not searched.
+                if args.quiet {
+                    totals.files_searched += 1;
+                    totals.files_matched += 1;
+                    totals.stats += stats;
+                    return false;
+                }
+                let path = display_path(file, args);
+                if let Err(e) = write!(out, "{path}{}", line_terminator(args)) {
+                    panic!("{e}");
+                }
+            }
+            stats
+        }
+        Outcome::Lines(_, stats) if args.count => {
+            if stats.matched_lines > 0 || args.include_zero {
+                let prefix = match args.file.len() {
+                    1 => String::new(),
+                    _ => format!("{}{}", display_path(file, args), args.filename_separator),
+                };
+                let count = stats.matched_lines;
+                if let Err(e) = write!(out, "{prefix}{count}{}", line_terminator(args)) {
+                    panic!("{e}");
+                }
+            }
+            stats
+        }
+        Outcome::Lines(lines, stats) => {
+            let separate = totals.files_matched > 0;
+            print_lines(
+                out,
+                file,
+                re,
+                args,
+                lines,
+                stats,
+                true,
+                separate,
+                &mut totals.unterminated,
+                &mut totals.printed,
+                report,
+            )
+        }
+        Outcome::Printed(stats) => stats,
+        Outcome::Failed(e) => {
+            messages::report(&format!("{file}: {e}"), args.no_messages);
+            totals.files_failed.push((file.to_string(), e));
+            return !past_deadline(args, totals);
+        }
+    };
+
+    totals.files_searched += 1;
+    if stats.matched_lines > 0 {
+        totals.files_matched += 1;
+        if args.count_summary {
+            totals.files.push((file.to_string(), stats));
+        }
+    }
+    if args.follow_lines {
+        totals.positions.insert(file.to_string(), stats.position);
+    }
+    totals.stats += stats;
+    complete(out, file, totals);
+    // The files searched concurrently are cancelled once the first match, or
+    // the last of --max-total, is printed.
+    let first_found = args.stop_after_first && stats.matched_lines > 0;
+    let limit_reached = args
+        .max_total
+        .is_some_and(|max| totals.stats.matched_lines >= max);
+    !first_found && !limit_reached && !past_deadline(args, totals)
+}
+
+/// Records `file` as completed with --checkpoint, once its results are
+/// flushed from `out`. Files that could not be read are not, so that they are
+/// searched again on --resume.
+fn complete(out: &mut impl Write, file: &str, totals: &mut Totals) {
+    if let Some(checkpoint) = &mut totals.checkpoint {
+        if let Err(e) = checkpoint.complete(out, file) {
+            panic!("{e}");
+        }
+    }
+}
+
+/// Returns whether --global-timeout has passed, in which case the remaining
+/// files are not searched, and records it in `totals`.
+fn past_deadline(args: &Args, totals: &mut Totals) -> bool {
+    let past = args
+        .deadline
+        .is_some_and(|deadline| Instant::now() >= deadline);
+    totals.timed_out |= past;
+    past
+}
+
+/// Returns the time at which the search of a file starting now gives up, with
+/// --timeout or --global-timeout.
+fn file_deadline(args: &Args) -> Option<Instant> {
+    let timeout = args.timeout.map(|timeout| Instant::now() + timeout);
+    match (timeout, args.deadline) {
+        (Some(timeout), Some(deadline)) => Some(timeout.min(deadline)),
+        (timeout, deadline) => timeout.or(deadline),
+    }
+}
+
+/// Keeps only the first `limit` matching `lines` of a file as matches, with
+/// --max-total, along with the context around them.
+fn limit_matches(lines: &mut Vec<Line>, stats: &mut Stats, limit: usize, re: &Regex, args: &Args) {
+    if stats.matched_lines <= limit {
+        return;
+    }
+    for line in lines.iter_mut().filter(|line| line.matched).skip(limit) {
+        line.matched = false;
+    }
+    retain_context(lines, stats, re, args);
+}
+
+/// The file name standing for stdin.
+const STDIN: &str = "-";
+
+/// Returns whether `path` names a file downloaded rather than opened.
+fn is_remote(path: &str) -> bool {
+    remote::is_url(path) || s3::is_url(path)
+}
+
+/// Returns whether `path` names a local file, as opposed to stdin or a
+/// remote file.
+fn is_local(path: &str) -> bool {
+    path != STDIN && !is_remote(path)
+}
+
+/// Returns whether `path` was last modified after `newer_than` and before
+/// `older_than`, when given. Stdin and remote files always are.
+fn is_modified_within(
+    path: &str,
+    newer_than: Option<SystemTime>,
+    older_than: Option<SystemTime>,
+) -> io::Result<bool> {
+    if (newer_than.is_none() && older_than.is_none()) || path == STDIN || is_remote(path) {
+        return Ok(true);
+    }
+
+    let modified = fs::metadata(path).and_then(|m| m.modified())?;
+    Ok(newer_than.is_none_or(|t| modified > t) && older_than.is_none_or(|t| modified < t))
+}
+
+/// Returns whether `path` is stdin read from a pipe, whose lines are printed
+/// as soon as they are selected rather than once it is read in full. Only
+/// lines printed as text, each matched on its own, can be.
+fn streams(path: &str, args: &Args) -> bool {
+    path == STDIN
+        && stdin_is_pipe()
+        && !args.json
+        && args.output_format == OutputFormat::Text
+        && args.histogram.is_none()
+        && args.group_by.is_none()
+        && !args.select
+        && !args.diff
+        && !args.write
+        && !args.count
+        && !args.files_with_matches
+        && !args.quiet
+        && args.expr.is_none()
+        && args.since.is_none()
+        && args.until.is_none()
+        && !args.function_context
+        && !args.show_heading
+        && args.record_separator.is_none()
+        && !args.paragraph
+        && !args.json_input
+        && !args.csv
+        && args.byte_range.is_none()
+        && args.line_range.is_none()
+        && args.encoding.is_none()
+        && args.max_total.is_none()
+}
+
+/// Returns whether stdin is a pipe.
+#[cfg(unix)]
+fn stdin_is_pipe() -> bool {
+    use std::os::fd::AsFd;
+    use std::os::unix::fs::FileTypeExt;
+
+    let Ok(fd) = io::stdin().as_fd().try_clone_to_owned() else {
+        return false;
+    };
+    File::from(fd)
+        .metadata()
+        .is_ok_and(|metadata| metadata.file_type().is_fifo())
+}
+
+#[cfg(not(unix))]
+fn stdin_is_pipe() -> bool {
+    false
+}
+
+/// Searches stdin for `re` as it arrives, printing and flushing every line as
+/// soon as it is selected. The lines are separated from those of earlier
+/// files when `separate` is set.
+fn stream_stdin(
+    out: &mut impl Write,
+    re: &Regex,
+    args: &Args,
+    separate: bool,
+    totals: &mut Totals,
+) -> io::Result<Stats> {
+    let after_context = args.after_context.or(args.context).unwrap_or_default();
+    let before_context = args.before_context.or(args.context).unwrap_or_default();
+    let start = Instant::now();
+    let bytes_re = match bytes::Regex::new(re.as_str()) {
+        Ok(re) => re,
+        Err(e) => panic!("{e}"),
+    };
+    let stdin = Deadline::new(io::stdin().lock(), file_deadline(args));
+    let mut source = LineReader::new(stdin, 0, 1, usize::MAX, args.max_line_size);
+
+    let mut heading = true;
+    let mut stats = stream::search_each(
+        &bytes_re,
+        &mut source,
+        before_context,
+        after_context,
+        args.max_count,
+        |line| {
+            print_lines(
+                out,
+                STDIN,
+                re,
+                args,
+                vec![line],
+                Stats::default(),
+                mem::take(&mut heading),
+                separate,
+                &mut totals.unterminated,
+                &mut totals.printed,
+                None,
+            );
+            if let Err(e) = out.flush() {
+                panic!("{e}");
+            }
+        },
+    );
+    stats.read_time = start.elapsed();
+    warn_skipped(STDIN, source.skipped(), args);
+    match source.take_error() {
+        Some(e) => Err(e),
+        None => Ok(stats),
+    }
+}
+
+/// Matches `re` against `path` itself, with --path-match, counting it as a
+/// single line.
+fn match_path(path: &str, re: &Regex) -> Stats {
+    let start = Instant::now();
+    let matches = re.find_iter(path).count();
+    Stats {
+        lines_searched: 1,
+        matched_lines: usize::from(matches > 0),
+        matches,
+        match_time: start.elapsed(),
+        ..Default::default()
+    }
+}
+
+/// Searches `path` for `re` as raw bytes, printing every match as a hex dump.
+fn hexdump_file(out: &mut impl Write, path: &str, re: &Regex, args: &Args) -> io::Result<Stats> {
+    let after_context = args.after_context.or(args.context).unwrap_or_default();
+    let before_context = args.before_context.or(args.context).unwrap_or_default();
+    let byte_range = args.byte_range.clone().unwrap_or(usize::MIN..=usize::MAX);
+
+    let start = Instant::now();
+    let contents;
+    let map;
+    let bytes = if is_local(path) {
+        map = map_file(&File::open(path)?);
+        &map[..]
+    } else {
+        contents = read_remote(path, args.timeout)?;
+        &contents[..]
+    };
+    let read_time = start.elapsed();
+
+    let start = Instant::now();
+    let re = match bytes::RegexBuilder::new(re.as_str()).unicode(false).build() {
+        Ok(re) => re,
+        Err(e) => panic!("{e}"),
+    };
+    let buf = slice_range(bytes, &byte_range);
+    let matches: Vec<_> = re
+        .find_iter(buf)
+        .take(args.max_count.unwrap_or(usize::MAX))
+        .map(|m| m.range())
+        .collect();
+    let mut stats = Stats {
+        matched_lines: matches.len(),
+        matches: matches.len(),
+        read_time,
+        match_time: start.elapsed(),
+        ..Default::default()
+    };
+
+    let start = Instant::now();
+    let result = hexdump::print(
+        out,
+        buf,
+        *byte_range.start(),
+        &matches,
+        before_context,
+        after_context,
+        use_color(args),
+    );
+    if let Err(e) = result {
+        panic!("{e}");
+    }
+    stats.print_time = start.elapsed();
+
+    Ok(stats)
+}
+
+/// Searches `path` for `re`, splitting it into `threads` chunks, and passes
+/// the lines selected for printing to `found` along with the statistics of
+/// the search, or the error that stopped the file from being read in full.
+/// The file is read unless its `contents` were read ahead of time.
+fn find_lines<R>(
+    path: &str,
+    contents: Option<Vec<u8>>,
+    re: &Regex,
+    args: &Args,
+    threads: usize,
+    found: impl FnOnce(io::Result<(Vec<Line>, Stats)>) -> R,
+) -> R {
+    let after_context = args.after_context.or(args.context).unwrap_or_default();
+    let before_context = args.before_context.or(args.context).unwrap_or_default();
+    let byte_range = args.byte_range.clone().unwrap_or(usize::MIN..=usize::MAX);
+
+    let start = Instant::now();
+    let deadline = file_deadline(args);
+    let mut f = match open_file(path, contents, args) {
+        Ok(f) => f,
+        Err(e) => return found(Err(e)),
+    };
+
+    let decoder = match decoder::find(path, &mut f) {
+        Ok(decoder) => decoder,
+        Err(e) => return found(Err(e)),
+    };
+
+    let map;
+    let contents: Vec<String>;
+    let mut lines;
+    let mut stats;
+    if use_mmap(&f, decoder, args) {
+        // Files read ahead of time are already in memory.
+        let buf = match &f {
+            Source::File(file) => {
+                map = map_file(file);
+                &map[..]
+            }
+            #[cfg(all(feature = "io_uring", target_os = "linux"))]
+            Source::Uring(reader) => {
+                map = map_file(reader.file());
+                &map[..]
+            }
+            Source::Bytes(bytes) => bytes.get_ref(),
+            Source::Text(_) => unreachable!("only decoded files are held as text"),
+        };
+        let read_time = start.elapsed();
+
+        let re = match bytes::RegexBuilder::new(re.as_str())
+            .multi_line(true)
+            .crlf(true)
+            .build()
+        {
+            Ok(re) => re,
+            Err(e) => panic!("{e}"),
+        };
+
+        let start = Instant::now();
+        let buf = slice_range(buf, &byte_range);
+        (lines, stats) = mmap::search(buf, &re, before_context, after_context, args.max_count);
+        // Offsets count from the start of the file rather than of the range.
+        for line in &mut lines {
+            line.offset += byte_range.start();
+        }
+        stats.read_time = read_time;
+        stats.match_time = start.elapsed();
+    } else if args.json_input || args.csv {
+        let source = match open_source(f, decoder, args.encoding) {
+            Ok(source) => source,
+            Err(e) => return found(Err(e)),
+        };
+        let result = match args.json_input {
+            true => find_json_values(source, re, threads, before_context, after_context),
+            false => find_csv_rows(path, source, re, args, before_context, after_context),
+        };
+        (lines, stats) = match result {
+            Ok(result) => result,
+            Err(e) => return found(Err(e)),
+        };
+    } else {
+        // Line numbers are 1-based, so a range starting at 0 starts at line 1.
+        let line_range = args.line_range.clone().unwrap_or(usize::MIN..=usize::MAX);
+        let first_line = (*line_range.start()).max(1);
+        let line_count = line_range.end().saturating_sub(first_line - 1);
+
+        let source = match open_source(f, decoder, args.encoding) {
+            Ok(source) => source,
+            Err(e) => return found(Err(e)),
+        };
+        let mut source = read_lines(source, &byte_range, first_line, line_count, args);
+
+        // Function context, headings and records can reach any distance
+        // back, so they need every line at hand.
+        let records = args.record_separator.is_some() || args.paragraph;
+        let sections = args.function_context || args.show_heading || records;
+        if (threads > 1 || sections) && args.max_count.is_none() {
+            let mut positions = Vec::new();
+            contents = iter::from_fn(|| {
+                source.next_line().map(|(number, offset, line)| {
+                    positions.push((number, offset));
+                    stream::to_string(line.to_vec())
+                })
+            })
+            .collect();
+            warn_skipped(path, source.skipped(), args);
+            if let Some(e) = source.take_error() {
+                return found(Err(e));
+            }
+            stats = Stats {
+                lines_searched: contents.len(),
+                read_time: start.elapsed(),
+                position: (source.number(), source.offset()),
+                ..Default::default()
+            };
+
+            let start = Instant::now();
+            let headers = match &args.record_separator {
+                Some(separator) => Some(record::starts(separator, &contents)),
+                None if args.paragraph => Some(record::paragraphs(&contents)),
+                None => sections.then(|| section_headers(path, &contents)),
+            };
+            let (matched, matches) = match &headers {
+                Some(starts) if records => record::match_records(re, &contents, starts),
+                // A single thread matches on this one, which is all
+                // WebAssembly has.
+                _ if threads <= 1 => match_lines(re, &contents),
+                _ => match_chunks(re, &contents, threads),
+            };
+            stats.matched_lines = matched.iter().filter(|&&m| m).count();
+            stats.matches = matches;
+            let sections = headers.as_deref().map(|headers| Sections {
+                headers,
+                extend: args.function_context || records,
+                show: args.show_heading,
+                whole: records,
+            });
+            lines = select_lines(
+                &contents,
+                &positions,
+                &matched,
+                sections.as_ref(),
+                source.terminated(),
+                before_context,
+                after_context,
+            );
+            stats.match_time = start.elapsed();
+        } else {
+            // Lines are matched as they are read, which also lets reading
+            // stop as soon as the limit is reached.
+            let re = match bytes::Regex::new(re.as_str()) {
+                Ok(re) => re,
+                Err(e) => panic!("{e}"),
+            };
+            (lines, stats) = stream::search(
+                &re,
+                &mut source,
+                before_context,
+                after_context,
+                args.max_count,
+            );
+            stats.read_time = start.elapsed();
+            warn_skipped(path, source.skipped(), args);
+            if let Some(e) = source.take_error() {
+                return found(Err(e));
+            }
+        }
+    }
+
+    // Searches that cannot be interrupted are only given up once complete.
+    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+        return found(Err(stream::timed_out()));
+    }
+    found(Ok((lines, stats)))
+}
+
+/// Parses `source` as JSON with --json-input and searches its string values
+/// for `re`, splitting the work into `threads` chunks. Returns the values
+/// selected for printing, each after its path and numbered as the line its
+/// document starts on, along with the statistics of the search.
+fn find_json_values(
+    mut source: Source,
+    re: &Regex,
+    threads: usize,
+    before_context: usize,
+    after_context: usize,
+) -> io::Result<(Vec<Line<'static>>, Stats)> {
+    let start = Instant::now();
+    let mut text = String::new();
+    source.read_to_string(&mut text)?;
+    let documents = json::parse_documents(&text).map_err(io::Error::other)?;
+    let mut positions = Vec::new();
+    let mut paths = Vec::new();
+    let mut values = Vec::new();
+    for (number, offset, document) in &documents {
+        for (path, value) in document.strings() {
+            positions.push((*number, *offset));
+            paths.push(path);
+            values.push(value.to_string());
+        }
+    }
+    let mut stats = Stats {
+        lines_searched: values.len(),
+        read_time: start.elapsed(),
+        ..Default::default()
+    };
+
+    let start = Instant::now();
+    let (matched, matches) = match threads {
+        0 | 1 => match_lines(re, &values),
+        _ => match_chunks(re, &values, threads),
+    };
+    stats.matched_lines = matched.iter().filter(|&&m| m).count();
+    stats.matches = matches;
+    // Values spanning several lines are printed on one, with their newlines
+    // escaped.
+    let lines = select_records(&positions, &matched, before_context, after_context, |j| {
+        format!("{}: {}", paths[j], json::escape(&values[j]))
+    });
+    stats.match_time = start.elapsed();
+
+    Ok((lines, stats))
+}
+
+/// Parses `source` as CSV with --csv and searches the fields of each row, or
+/// only those of --columns, for `re`. Returns the rows selected for printing
+/// along with the statistics of the search.
+fn find_csv_rows(
+    path: &str,
+    mut source: Source,
+    re: &Regex,
+    args: &Args,
+    before_context: usize,
+    after_context: usize,
+) -> io::Result<(Vec<Line<'static>>, Stats)> {
+    let start = Instant::now();
+    let mut text = String::new();
+    source.read_to_string(&mut text)?;
+    let delimiter = match args.delimiter {
+        Some(delimiter) => delimiter,
+        None if path.to_ascii_lowercase().ends_with(".tsv") => '\t',
+        None => ',',
+    };
+    let rows = csv::parse(&text, delimiter).map_err(io::Error::other)?;
+    let Some((header, rows)) = rows.split_first() else {
+        return Ok((Vec::new(), Stats::default()));
+    };
+    let columns = match args.columns.is_empty() {
+        true => (0..header.fields.len()).collect(),
+        false => csv::column_indices(header, &args.columns).map_err(io::Error::other)?,
+    };
+    let mut stats = Stats {
+        lines_searched: rows.len(),
+        read_time: start.elapsed(),
+        ..Default::default()
+    };
+
+    let start = Instant::now();
+    let mut matched = Vec::with_capacity(rows.len());
+    for row in rows {
+        let fields = columns.iter().filter_map(|&i| row.fields.get(i));
+        let matches: usize = fields.map(|field| re.find_iter(field).count()).sum();
+        matched.push(matches > 0);
+        stats.matches += matches;
+    }
+    stats.matched_lines = matched.iter().filter(|&&m| m).count();
+    let positions: Vec<_> = rows.iter().map(|row| (row.number, row.offset)).collect();
+    let lines = select_records(&positions, &matched, before_context, after_context, |j| {
+        rows[j].text.to_string()
+    });
+    stats.match_time = start.elapsed();
+
+    Ok((lines, stats))
+}
+
+/// Selects the matching records of a structured file along with their
+/// context, where the number and offset of each record are given by
+/// `positions` and its text by `text`.
+fn select_records(
+    positions: &[(usize, usize)],
+    matched: &[bool],
+    before_context: usize,
+    after_context: usize,
+    text: impl Fn(usize) -> String,
+) -> Vec<Line<'static>> {
+    let intervals = create_intervals(matched, None, false, before_context, after_context);
+    intervals
+        .iter()
+        .flat_map(Interval::iter)
+        .map(|j| Line {
+            number: positions[j].0,
+            offset: positions[j].1,
+            text: Cow::Owned(text(j)),
+            matched: matched[j],
+            terminated: true,
+            header: false,
+        })
+        .collect()
+}
+
+/// Returns whether to search `f` memory-mapped rather than split into lines.
+/// Without --mmap or --no-mmap, files are mapped when `mmap::is_preferred`
+/// and none of the options requiring lines is given.
+fn use_mmap(f: &Source, decoder: Option<&decoder::Decoder>, args: &Args) -> bool {
+    if decoder.is_some() || args.no_mmap {
+        return false;
+    }
+    if args.mmap {
+        return true;
+    }
+    // WebAssembly cannot map files, so mapping them only reads them whole.
+    !cfg!(target_family = "wasm")
+        && args.line_range.is_none()
+        && args.encoding.is_none()
+        && !args.function_context
+        && !args.show_heading
+        && args.record_separator.is_none()
+        && !args.paragraph
+        && !args.json_input
+        && !args.csv
+        && f.file().is_some_and(mmap::is_preferred)
+}
+
+/// Prints the `lines` selected from `path` as requested by `args`, and
+/// returns `stats` completed with the time spent printing. With --heading,
+/// the name of the file is printed above them when `heading` is set, and with
+/// --pretty, they are separated from those of earlier files when `separate`
+/// is set.
+/// When the last line is not terminated in its file, it is left unterminated
+/// and `unterminated` is set until more text follows it. With --unique, the
+/// lines already in `printed` are left out. With --output-format, they are
+/// written to `report`, with --diff, as a diff of their replacements, and
+/// with --write, replaced in the file itself.
+#[allow(clippy::too_many_arguments)]
+fn print_lines(
+    out: &mut impl Write,
+    path: &str,
+    re: &Regex,
+    args: &Args,
+    mut lines: Vec<Line>,
+    mut stats: Stats,
+    heading: bool,
+    separate: bool,
+    unterminated: &mut bool,
+    printed: &mut HashSet<String>,
+    report: Option<&mut Report>,
+) -> Stats {
+    let start = Instant::now();
+    let result = if args.json {
+        printer::print_json(out, path, re, &lines)
+    } else if let Some(report) = report {
+        report.file(out, path, re, &lines)
+    } else if let (true, Some(template)) = (args.write, &args.replace) {
+        let replace = |text: &str| template.replace_all(re, text);
+        let backup = edit::Backup {
+            suffix: args.backup_suffix.as_deref(),
+            dir: args.backup_dir.as_deref(),
+        };
+        edit::write(path, &lines, replace, &backup)
+            .map_err(|e| io::Error::new(e.kind(), format!("{path}: {e}")))
+    } else if let (true, Some(template)) = (args.diff, &args.replace) {
+        let path = display_path(path, args);
+        let replace = |text: &str| template.replace_all(re, text);
+        diff::write(out, &path, &lines, replace, use_color(args))
+    } else {
+        // Prefix lines with the file name when more than one file is searched,
+        // unless it is printed as a heading.
+        let prefix = match args.file.len() {
+            _ if args.heading => String::new(),
+            1 => String::new(),
+            _ => format!("{}{}", display_path(path, args), args.filename_separator),
+        };
+        // Replacements are not colored, since the matches are gone, and
+        // neither are the matches printed on their own.
+        let color = use_color(args) && args.replace.is_none() && !args.only_matching;
+        if args.only_matching {
+            lines = only_matching(&lines, re, args);
+        } else if let Some(template) = &args.replace {
+            for line in lines.iter_mut().filter(|line| line.matched) {
+                line.text = Cow::Owned(template.replace_all(re, &line.text));
+            }
+        }
+        if args.unique {
+            lines.retain(|line| printed.insert(line.text.to_string()));
+        }
+        let highlighter = Highlighter {
+            re,
+            groups: &args.highlight_group,
+        };
+        if !lines.is_empty() && mem::take(unterminated) {
+            if let Err(e) = write!(out, "{}", line_terminator(args)) {
+                panic!("{e}");
+            }
+        }
+        if args.heading && heading && !lines.is_empty() {
+            let path = display_path(path, args);
+            if let Err(e) = printer::print_heading(out, &path, separate, use_color(args)) {
+                panic!("{e}");
+            }
+        }
+        *unterminated = lines.last().is_some_and(|line| !line.terminated);
+        let gutter = Gutter {
+            line_number: args.line_number,
+            byte_offset: args.byte_offset,
+            width: args.line_number_width,
+            separator: &args.line_number_separator,
+        };
+        // Only context is printed in blocks, rather than matching lines alone.
+        let context = [args.after_context, args.before_context, args.context];
+        let blocks = context.into_iter().flatten().any(|n| n > 0) || args.function_context;
+        printer::print_text(
+            out,
+            &prefix,
+            &gutter,
+            &lines,
+            color.then_some(&highlighter),
+            line_terminator(args),
+            blocks && !args.only_matching,
+        )
+    };
+    if let Err(e) = result {
+        panic!("{e}");
+    }
+    stats.print_time = start.elapsed();
+
+    if args.json {
+        if let Err(e) = json::end(out, path, stats) {
+            panic!("{e}");
+        }
+    }
+
+    stats
+}
+
+/// Returns `path` as printed in text output, with its directories separated
+/// by --path-separator when given.
+fn display_path<'a>(path: &'a str, args: &Args) -> Cow<'a, str> {
+    match args.path_separator {
+        Some(separator) => path
+            .chars()
+            .map(|c| {
+                if std::path::is_separator(c) {
+                    separator
+                } else {
+                    c
+                }
+            })
+            .collect(),
+        None => Cow::Borrowed(path),
+    }
+}
+
+/// Returns the terminator of every line printed.
+fn line_terminator(args: &Args) -> char {
+    if args.print0 {
+        '\0'
+    } else {
+        '\n'
+    }
+}
+
+/// Replaces the prefixes in object storage among the files given in `args`
+/// by the objects below them. Returns the prefixes that cannot be listed
+/// along with the error.
+fn list_objects(args: &mut Args) -> Vec<(String, io::Error)> {
+    let mut errors = Vec::new();
+    if !args.file.iter().any(|file| s3::is_url(file)) {
+        return errors;
+    }
+    let mut files = Vec::with_capacity(args.file.len());
+    for file in mem::take(&mut args.file) {
+        if !s3::is_url(&file) {
+            files.push(file);
+            continue;
+        }
+        match s3::list(&file) {
+            Ok(objects) => files.extend(objects),
+            Err(e) => errors.push((file, e)),
+        }
+    }
+    args.file = files;
+    errors
+}
+
+/// Settles the output options left to their defaults, so that every printer
+/// follows the same decision. When printing to a terminal, or with --pretty,
+/// matches are colored, lines are numbered and the name of each file is
+/// printed above its lines. Otherwise the output is plain, with the name of
+/// the file on every line. Options given explicitly always apply.
+fn resolve_output(args: &mut Args) {
+    let terminal = args.output.is_none() && !args.print0 && io::stdout().is_terminal();
+    let interactive = args.pretty || terminal;
+    if args.color == ColorChoice::Auto {
+        args.color = match interactive {
+            true => ColorChoice::Always,
+            false => ColorChoice::Never,
+        };
+    }
+    args.line_number |= interactive && !args.no_line_number;
+    args.heading |= interactive && !args.no_heading;
+}
+
+/// Returns whether matches should be colored, as settled by
+/// `resolve_output`.
+fn use_color(args: &Args) -> bool {
+    args.color == ColorChoice::Always
+}
+
+/// Memory maps the file `f`.
+#[cfg(not(target_family = "wasm"))]
+fn map_file(f: &File) -> Mmap {
+    // SAFETY: the mapping is only read, and like any other reader we make no
+    // guarantees about files that are modified during the search.
+    match unsafe { Mmap::map(f) } {
+        Ok(map) => map,
+        Err(e) => panic!("{e}"),
+    }
+}
+
+/// Reads the file `f` whole, where files cannot be memory mapped.
+#[cfg(target_family = "wasm")]
+fn map_file(mut f: &File) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    if let Err(e) = f.read_to_end(&mut bytes) {
+        panic!("{e}");
+    }
+    bytes
+}
+
+/// Returns the bytes of `buf` within `byte_range`.
+fn slice_range<'a>(buf: &'a [u8], byte_range: &RangeInclusive<usize>) -> &'a [u8] {
+    let Some(last) = buf.len().checked_sub(1) else {
+        return buf;
+    };
+    let range = Interval::try_from(byte_range.clone()).ok();
+    match range.and_then(|range| range.intersect(&Interval::new(0, last))) {
+        Some(interval) => &buf[RangeInclusive::from(interval)],
+        None => &[],
+    }
+}
+
+/// Splits the matching lines into one line per match, holding either the
+/// match itself, the capture group selected by `args` or the expansion of the
+/// replacement template. Context lines are dropped.
+fn only_matching<'a>(lines: &[Line], re: &Regex, args: &Args) -> Vec<Line<'a>> {
+    let mut matches = Vec::new();
+    for line in lines.iter().filter(|line| line.matched) {
+        for caps in re.captures_iter(&line.text) {
+            // Like grep, empty matches are not printed.
+            if caps[0].is_empty() {
+                continue;
+            }
+
+            let text = match (&args.replace, &args.group) {
+                (Some(template), _) => {
+                    let mut expansion = String::new();
+                    template.expand(&caps, &mut expansion);
+                    expansion
+                }
+                (None, Some(group)) => {
+                    let m = match group.parse::<usize>() {
+                        Ok(group) => caps.get(group),
+                        Err(_) => caps.name(group),
+                    };
+                    match m {
+                        Some(m) => m.as_str().to_string(),
+                        None => continue,
+                    }
+                }
+                (None, None) => caps[0].to_string(),
+            };
+
+            matches.push(Line {
+                header: false,
+                number: line.number,
+                offset: line.offset + caps.get(0).map_or(0, |m| m.start()),
+                text: Cow::Owned(text),
+                matched: true,
+                terminated: true,
+            });
+        }
+        // The output of a line ends the way the line itself did.
+        if let Some(last) = matches.last_mut().filter(|last| last.number == line.number) {
+            last.terminated = line.terminated;
+        }
+    }
+
+    matches
+}
+
+/// The contents of a file, either read directly, read ahead of time or
+/// decoded to text first.
+enum Source {
+    File(File),
+    #[cfg(all(feature = "io_uring", target_os = "linux"))]
+    Uring(Box<uring::UringReader>),
+    Bytes(Cursor<Vec<u8>>),
+    Text(Cursor<String>),
+}
+
+impl Source {
+    /// Returns the file being read, unless its contents are already in
+    /// memory.
+    fn file(&self) -> Option<&File> {
+        match self {
+            Source::File(f) => Some(f),
+            #[cfg(all(feature = "io_uring", target_os = "linux"))]
+            Source::Uring(reader) => Some(reader.file()),
+            Source::Bytes(_) | Source::Text(_) => None,
+        }
+    }
+}
+
+impl Read for Source {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Source::File(f) => f.read(buf),
+            #[cfg(all(feature = "io_uring", target_os = "linux"))]
+            Source::Uring(reader) => reader.read(buf),
+            Source::Bytes(bytes) => bytes.read(buf),
+            Source::Text(text) => text.read(buf),
+        }
+    }
+}
+
+impl Seek for Source {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Source::File(f) => f.seek(pos),
+            #[cfg(all(feature = "io_uring", target_os = "linux"))]
+            Source::Uring(reader) => reader.seek(pos),
+            Source::Bytes(bytes) => bytes.seek(pos),
+            Source::Text(text) => text.seek(pos),
+        }
+    }
+}
+
+/// Opens the file at `path`, downloading it within --timeout when it is a URL,
+/// or wraps its `contents` when they were read ahead of time.
+fn open_file(path: &str, contents: Option<Vec<u8>>, args: &Args) -> io::Result<Source> {
+    match contents {
+        Some(contents) => Ok(Source::Bytes(Cursor::new(contents))),
+        None if is_local(path) => File::open(path).map(file_source),
+        None => Ok(Source::Bytes(Cursor::new(read_remote(path, args.timeout)?))),
+    }
+}
+
+/// Reads all of `path`, which is stdin or a remote file rather than a local
+/// file. Downloading a URL gives up after `timeout`.
+fn read_remote(path: &str, timeout: Option<Duration>) -> io::Result<Vec<u8>> {
+    if path == STDIN {
+        read_stdin()
+    } else if s3::is_url(path) {
+        s3::fetch(path)
+    } else {
+        remote::fetch(path, timeout)
+    }
+}
+
+/// Reads all of stdin. It is held in memory, since searching seeks within
+/// it.
+fn read_stdin() -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    io::stdin().lock().read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Reads `f` through io_uring, unless it is unavailable.
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+fn file_source(f: File) -> Source {
+    match uring::open(f) {
+        Ok(reader) => Source::Uring(Box::new(reader)),
+        Err(f) => Source::File(f),
+    }
+}
+
+#[cfg(not(all(feature = "io_uring", target_os = "linux")))]
+fn file_source(f: File) -> Source {
+    Source::File(f)
+}
+
+/// Returns the contents of `f`, converted to text by `decoder` or transcoded
+/// from `encoding` when given. Ranges of decoded documents and transcoded
+/// files apply to the decoded text.
+fn open_source(
+    mut f: Source,
+    decoder: Option<&decoder::Decoder>,
+    encoding: Option<Encoding>,
+) -> io::Result<Source> {
+    let text = match (decoder, encoding) {
+        (Some(decoder), _) => decoder.decode(&mut f).map_err(io::Error::other)?,
+        (None, Some(encoding)) => {
+            let mut bytes = Vec::new();
+            f.read_to_end(&mut bytes)?;
+            encoding.decode(&bytes)
+        }
+        (None, None) => return Ok(f),
+    };
+    Ok(Source::Text(Cursor::new(text)))
+}
+
+/// Searches `path` for the first line matching `re`, without reading any
+/// further. The returned statistics only cover the lines read up to that
+/// point, and the time spent reading is counted as matching since the two
+/// are interleaved. The file is read unless its `contents` were read ahead
+/// of time.
+fn find_first(path: &str, contents: Option<Vec<u8>>, re: &Regex, args: &Args) -> io::Result<Stats> {
+    let byte_range = args.byte_range.clone().unwrap_or(usize::MIN..=usize::MAX);
+    let line_range = args.line_range.clone().unwrap_or(usize::MIN..=usize::MAX);
+    let first_line = (*line_range.start()).max(1);
+    let line_count = line_range.end().saturating_sub(first_line - 1);
+
+    let start = Instant::now();
+    let mut f = open_file(path, contents, args)?;
+    let decoder = decoder::find(path, &mut f)?;
+
+    let re = match bytes::Regex::new(re.as_str()) {
+        Ok(re) => re,
+        Err(e) => panic!("{e}"),
+    };
+    let source = open_source(f, decoder, args.encoding)?;
+    let mut source = read_lines(source, &byte_range, first_line, line_count, args);
+    let mut stats = stream::find_first(&re, &mut source);
+    stats.match_time = start.elapsed();
+    warn_skipped(path, source.skipped(), args);
+
+    // Without a match, the rest of the file might have held one.
+    match source.take_error() {
+        Some(e) if stats.matched_lines == 0 => Err(e),
+        _ => Ok(stats),
+    }
+}
+
+/// Reads `line_count` lines of `source`, starting at line `first_line` of the
+/// bytes within `byte_range`. Lines are read lazily, one at a time, and those
+/// longer than --max-line-size are skipped. Reading fails once the deadline of
+/// the file passes.
+fn read_lines(
+    mut source: impl Read + Seek,
+    byte_range: &RangeInclusive<usize>,
+    first_line: usize,
+    line_count: usize,
+    args: &Args,
+) -> LineReader<impl Read> {
+    // Seek past the skipped prefix rather than reading it.
+    if let Err(e) = source.seek(SeekFrom::Start(*byte_range.start() as u64)) {
+        panic!("{e}");
+    }
+    let limit = (byte_range.end() - byte_range.start()).saturating_add(1);
+    LineReader::new(
+        Deadline::new(source.take(limit as u64), file_deadline(args)),
+        *byte_range.start(),
+        first_line,
+        line_count,
+        args.max_line_size,
+    )
+}
+
+/// Warns that `skipped` lines of `path` were longer than --max-line-size and
+/// were not searched.
+fn warn_skipped(path: &str, skipped: usize, args: &Args) {
+    if skipped > 0 {
+        let message = format!(
+            "warning: {path}: skipped {skipped} lines longer than {} bytes",
+            args.max_line_size
+        );
+        messages::report(&message, false);
+    }
+}
+
+/// Determines which of `lines` match `re`, splitting the work into `threads`
+/// chunks that are searched concurrently. Returns a flag per line, in order,
+/// along with the total number of matches.
+#[cfg(not(feature = "rayon"))]
+fn match_chunks(re: &Regex, lines: &[String], threads: usize) -> (Vec<bool>, usize) {
+    let chunk_size = lines.len().div_ceil(threads.max(1)).max(1);
+
+    thread::scope(|s| {
+        let handles: Vec<_> = lines
+            .chunks(chunk_size)
+            .map(|chunk| s.spawn(|| match_lines(re, chunk)))
+            .collect();
+
+        // Joining in spawn order stitches the chunks back together in order.
+        let mut matched = Vec::with_capacity(lines.len());
+        let mut matches = 0;
+        for handle in handles {
+            let (chunk_matched, chunk_matches) = handle.join().unwrap();
+            matched.extend(chunk_matched);
+            matches += chunk_matches;
+        }
+        (matched, matches)
+    })
+}
+
+/// Determines which of `lines` match `re`, splitting the work into `threads`
+/// chunks that are searched as rayon tasks. Returns a flag per line, in
+/// order, along with the total number of matches.
+#[cfg(feature = "rayon")]
+fn match_chunks(re: &Regex, lines: &[String], threads: usize) -> (Vec<bool>, usize) {
+    use rayon::prelude::*;
+
+    let chunk_size = lines.len().div_ceil(threads.max(1)).max(1);
+    lines
+        .par_chunks(chunk_size)
+        .map(|chunk| match_lines(re, chunk))
+        .reduce(
+            || (Vec::new(), 0),
+            |(mut matched, matches), (chunk_matched, chunk_matches)| {
+                matched.extend(chunk_matched);
+                (matched, matches + chunk_matches)
+            },
+        )
+}
+
+/// Determines which of `lines` match `re`. Returns a flag per line along with
+/// the total number of matches.
+fn match_lines(re: &Regex, lines: &[String]) -> (Vec<bool>, usize) {
+    let matched: Vec<bool> = lines.iter().map(|line| re.is_match(line)).collect();
+    let matches = lines
+        .iter()
+        .zip(&matched)
+        .filter(|(_, &m)| m)
+        .map(|(line, _)| re.find_iter(line).count())
+        .sum();
+
+    (matched, matches)
+}
+
+/// Selects the matching lines along with their context. Each line is selected
+/// at most once, even when the context of neighbouring matches overlaps.
+/// The number and offset of each line are given by `positions`, and the last
+/// of them ended with a newline when `terminated` is set. The `sections` of
+/// the file may extend the context or add the header of each block.
+fn select_lines<'a>(
+    lines: &'a [String],
+    positions: &[(usize, usize)],
+    matched: &[bool],
+    sections: Option<&Sections>,
+    terminated: bool,
+    before_context: usize,
+    after_context: usize,
+) -> Vec<Line<'a>> {
+    let headers = sections.filter(|s| s.extend).map(|s| s.headers);
+    let whole = sections.is_some_and(|s| s.whole);
+    let intervals = create_intervals(matched, headers, whole, before_context, after_context);
+
+    let line = |j: usize, header: bool| Line {
+        number: positions[j].0,
+        offset: positions[j].1,
+        text: Cow::Borrowed(lines[j].as_str()),
+        matched: matched[j],
+        terminated: j + 1 < lines.len() || terminated,
+        header,
+    };
+    let mut selected = Vec::with_capacity(intervals.iter().map(Interval::len).sum());
+    // The header last printed or selected, which later blocks within the
+    // same section do not repeat.
+    let mut last_header = None;
+    for interval in &intervals {
+        if let Some(sections) = sections.filter(|s| s.show) {
+            let header = sections.headers[..=interval.start].iter().rposition(|&h| h);
+            if header.is_some_and(|h| h < interval.start && Some(h) != last_header) {
+                selected.push(line(header.unwrap(), true));
+            }
+            last_header = header;
+        }
+        for j in interval.iter() {
+            if sections.is_some_and(|s| s.headers[j]) {
+                last_header = Some(j);
+            }
+            selected.push(line(j, false));
+        }
+    }
+
+    selected
+}
+
+/// The lines of a file starting a function or section, and how they are
+/// used.
+struct Sections<'a> {
+    /// Whether each line starts a section.
+    headers: &'a [bool],
+    /// Whether the context of each match extends back to its header, with
+    /// --function-context.
+    extend: bool,
+    /// Whether the header of each block of lines is printed above it, with
+    /// --show-heading.
+    show: bool,
+    /// Whether the context of each match also extends forward to the end of
+    /// its section, which is a record with --record-separator or a paragraph
+    /// with --paragraph.
+    whole: bool,
+}
+
+/// Returns the set of line indices covered by every match along with its
+/// context, which reaches back at least to the nearest of the `headers` at
+/// or above the match when given, and with `whole`, forward at least to the
+/// line before the next of them.
+fn create_intervals(
+    matched: &[bool],
+    headers: Option<&[bool]>,
+    whole: bool,
+    before_context: usize,
+    after_context: usize,
+) -> IntervalSet<usize> {
+    let last_line = matched.len().saturating_sub(1);
+    let mut header = None;
+    let mut intervals = IntervalSet::new();
+    for i in 0..matched.len() {
+        if headers.is_some_and(|headers| headers[i]) {
+            header = Some(i);
+        }
+        if matched[i] {
+            let mut interval =
+                Interval::new(i, i).expand(before_context, after_context, Some(last_line));
+            interval.start = interval.start.min(header.unwrap_or(i));
+            if let Some(headers) = headers.filter(|_| whole) {
+                let end = (i + 1..matched.len())
+                    .find(|&j| headers[j])
+                    .map_or(last_line, |next| next - 1);
+                interval.end = interval.end.max(end);
+            }
+            intervals.insert(interval);
+        }
+    }
+    intervals
+}
+
+/// Flags the `lines` of `path` that look like the first line of a function
+/// or section in its language.
+fn section_headers(path: &str, lines: &[String]) -> Vec<bool> {
+    let re = match Regex::new(lang::header(path)) {
+        Ok(re) => re,
+        Err(e) => panic!("{e}"),
+    };
+    lines.iter().map(|line| re.is_match(line)).collect()
 }
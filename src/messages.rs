@@ -0,0 +1,37 @@
+//! Diagnostics about individual files, such as files that cannot be read or
+//! decoded.
+//!
+//! They are printed to stderr, or appended to the file given with
+//! --error-log instead, so that a scan expected to skip many files keeps its
+//! terminal clean. Files are searched on several threads, so the log is
+//! shared by all of them.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::{Mutex, OnceLock};
+
+static ERROR_LOG: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// Appends every later message to the file at `path`, creating it if needed.
+pub fn open_log(path: &str) -> io::Result<()> {
+    let f = OpenOptions::new().create(true).append(true).open(path)?;
+    // Only main opens the log, once.
+    let _ = ERROR_LOG.set(Mutex::new(f));
+    Ok(())
+}
+
+/// Reports `message`, to the error log when one is open or else to stderr
+/// unless `quiet`. The log receives every message, since nobody watches it
+/// as it is written.
+pub fn report(message: &str, quiet: bool) {
+    match ERROR_LOG.get() {
+        Some(log) => {
+            let mut f = log.lock().unwrap_or_else(|e| e.into_inner());
+            if let Err(e) = writeln!(f, "{message}") {
+                panic!("{e}");
+            }
+        }
+        None if !quiet => eprintln!("{message}"),
+        None => {}
+    }
+}
@@ -0,0 +1,199 @@
+//! Byte-oriented search over a memory-mapped file.
+//!
+//! Rather than splitting the whole file into lines, the regex is run over the
+//! entire buffer and only the lines containing a match, along with their
+//! context, are located. Line numbers are computed by counting the newlines
+//! preceding each match with `memchr`, which is vectorized on most platforms,
+//! so the cost of numbering lines stays low even for very large files.
+
+use crate::printer::Line;
+use crate::stats::Stats;
+use memchr::{memchr, memchr_iter, memrchr};
+use regex::bytes::Regex;
+use std::fs::{self, File};
+
+/// Files smaller than this are read faster than they are mapped, since
+/// setting up and tearing down the mapping outweighs the copies it saves.
+const MIN_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Returns whether `f` is expected to be searched faster memory-mapped than
+/// split into lines. Large files are mapped, unless they are on a network
+/// filesystem, where every page fault waits on the network, or they would
+/// take up more than half of the memory available.
+pub fn is_preferred(f: &File) -> bool {
+    let Ok(metadata) = f.metadata() else {
+        return false;
+    };
+    if !metadata.is_file() || metadata.len() < MIN_SIZE || is_network_fs(f) {
+        return false;
+    }
+    available_memory().is_none_or(|available| metadata.len() <= available / 2)
+}
+
+/// Returns whether `f` is on a network filesystem, as reported by
+/// `fstatfs`.
+#[cfg(target_os = "linux")]
+fn is_network_fs(f: &File) -> bool {
+    use std::os::fd::AsRawFd;
+
+    const NETWORK_FS: &[u32] = &[
+        0x6969,     // NFS
+        0x517b,     // SMB
+        0xff534d42, // CIFS
+        0xfe534d42, // SMB2
+        0x00c36400, // Ceph
+        0x5346414f, // AFS
+        0x65735546, // FUSE, as used by sshfs
+    ];
+
+    let mut stat = std::mem::MaybeUninit::<libc::statfs>::uninit();
+    // SAFETY: `stat` is only read once `fstatfs` has filled it in.
+    if unsafe { libc::fstatfs(f.as_raw_fd(), stat.as_mut_ptr()) } != 0 {
+        return false;
+    }
+    let kind = unsafe { stat.assume_init() }.f_type;
+    NETWORK_FS.contains(&(kind as u32))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_network_fs(_f: &File) -> bool {
+    false
+}
+
+/// Returns the number of bytes of memory available, as reported by
+/// `/proc/meminfo`, or `None` where it cannot be found.
+fn available_memory() -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo
+        .lines()
+        .find(|line| line.starts_with("MemAvailable:"))?;
+    let kb = line.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+    Some(kb * 1024)
+}
+
+/// Tracks the number of the line starting at `offset`, so that numbering a
+/// later line only requires counting the newlines in between.
+struct LineCounter {
+    offset: usize,
+    number: usize,
+}
+
+impl LineCounter {
+    /// Returns the number of the line containing `offset`, which must not be
+    /// before the offset of a previous call.
+    fn line_number(&mut self, buf: &[u8], offset: usize) -> usize {
+        self.number += memchr_iter(b'\n', &buf[self.offset..offset]).count();
+        self.offset = offset;
+        self.number
+    }
+}
+
+/// Searches `buf` for `re`, which must have been built with multi-line mode
+/// enabled so that `^` and `$` match at line boundaries. Returns the matching
+/// lines with `before_context` and `after_context` lines of context, in order
+/// and without duplicates, along with the statistics of the search. With a
+/// `max_count`, the search stops after that many matching lines.
+pub fn search<'a>(
+    buf: &'a [u8],
+    re: &Regex,
+    before_context: usize,
+    after_context: usize,
+    max_count: Option<usize>,
+) -> (Vec<Line<'a>>, Stats) {
+    let mut stats = Stats::default();
+    let mut counter = LineCounter {
+        offset: 0,
+        number: 1,
+    };
+
+    // The number, start and end offsets of every matching line.
+    let mut matches = Vec::new();
+    let mut pos = 0;
+    while pos < buf.len() && max_count.is_none_or(|max| matches.len() < max) {
+        let Some(m) = re.find_at(buf, pos) else {
+            break;
+        };
+        let start = line_start(buf, m.start());
+        let end = line_end(buf, m.start());
+        pos = end + 1;
+
+        // A match can run past the end of its line (e.g. `\s` matching the
+        // newline), in which case the line has to match on its own.
+        let line = trim_cr(&buf[start..end]);
+        if m.end() > start + line.len() && !re.is_match(line) {
+            continue;
+        }
+
+        stats.matches += re.find_iter(line).count();
+        matches.push((counter.line_number(buf, start), start, end));
+    }
+
+    stats.matched_lines = matches.len();
+    stats.lines_searched = match buf.last() {
+        None => 0,
+        Some(b'\n') => counter.line_number(buf, buf.len()) - 1,
+        Some(_) => counter.line_number(buf, buf.len()),
+    };
+    stats.position = (stats.lines_searched, buf.len());
+
+    let mut lines = Vec::new();
+
+    // Number of the first line that has not been selected yet.
+    let mut next = 1;
+    for (i, &(number, start, end)) in matches.iter().enumerate() {
+        let mut before = Vec::new();
+        let mut cursor = start;
+        for number in (number.saturating_sub(before_context).max(next)..number).rev() {
+            let end = cursor - 1;
+            cursor = line_start(buf, end);
+            before.push(line(buf, number, cursor, end, false));
+        }
+        lines.extend(before.into_iter().rev());
+        lines.push(line(buf, number, start, end, true));
+
+        // After context stops short of the next match, whose own before
+        // context picks up from there.
+        let last = match matches.get(i + 1) {
+            Some(&(next_match, _, _)) => (number + after_context).min(next_match - 1),
+            None => number + after_context,
+        };
+        let mut cursor = end;
+        let mut number = number;
+        while number < last && cursor + 1 < buf.len() {
+            let start = cursor + 1;
+            cursor = line_end(buf, start);
+            number += 1;
+            lines.push(line(buf, number, start, cursor, false));
+        }
+        next = number + 1;
+    }
+
+    (lines, stats)
+}
+
+fn line(buf: &[u8], number: usize, start: usize, end: usize, matched: bool) -> Line<'_> {
+    Line {
+        number,
+        offset: start,
+        text: String::from_utf8_lossy(trim_cr(&buf[start..end])),
+        matched,
+        terminated: end < buf.len(),
+        header: false,
+    }
+}
+
+/// Returns the offset of the start of the line containing `offset`.
+fn line_start(buf: &[u8], offset: usize) -> usize {
+    memrchr(b'\n', &buf[..offset]).map_or(0, |i| i + 1)
+}
+
+/// Returns the offset of the newline ending the line containing `offset`, or
+/// the length of `buf` if the line is not terminated.
+fn line_end(buf: &[u8], offset: usize) -> usize {
+    memchr(b'\n', &buf[offset..]).map_or(buf.len(), |i| offset + i)
+}
+
+/// Removes the carriage return of a CRLF line ending.
+fn trim_cr(line: &[u8]) -> &[u8] {
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
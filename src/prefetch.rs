@@ -0,0 +1,109 @@
+//! Reading files ahead of the search with asynchronous I/O.
+//!
+//! On network filesystems the latency of each read dominates, so rather than
+//! reading one file at a time, the async backend keeps many reads in flight
+//! on a tokio runtime and hands each file over as soon as it is read. Files
+//! too large to be held in memory by the dozen are left to be read as they
+//! are searched. It is enabled by the `tokio` cargo feature.
+
+use clap::ValueEnum;
+#[cfg(feature = "tokio")]
+use std::io;
+#[cfg(feature = "tokio")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "tokio")]
+use std::sync::mpsc;
+#[cfg(feature = "tokio")]
+use std::time::Duration;
+
+/// How files are read.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum IoMode {
+    /// Blocking reads, one file at a time. With the io_uring feature, the
+    /// reads of each file are batched through io_uring when available.
+    Sync,
+    /// Many reads kept in flight at once, ahead of the search.
+    Async,
+}
+
+/// Maximum number of files being read, or read and waiting to be searched,
+/// at the same time.
+#[cfg(feature = "tokio")]
+const IN_FLIGHT: usize = 64;
+
+/// Size in bytes of the largest local file read ahead, so that the files in
+/// flight take at most half a gigabyte of memory.
+#[cfg(feature = "tokio")]
+const MAX_SIZE: u64 = 8 * 1024 * 1024;
+
+/// The contents of a file read ahead of the search, or `None` when it is
+/// larger than `MAX_SIZE` and left to be read as it is searched. It counts
+/// against the files in flight until it is dropped.
+#[cfg(feature = "tokio")]
+pub struct Prefetched {
+    pub contents: io::Result<Option<Vec<u8>>>,
+    _permit: tokio::sync::OwnedSemaphorePermit,
+}
+
+/// Reads every file in `paths`, sending the contents of each to `tx` along
+/// with its index as soon as it is read. At most `IN_FLIGHT` files are read
+/// or held at once, and no further reads are started once `done` is set.
+/// Stdin and remote files are read by `read_remote`, on threads where it may
+/// block, giving up downloads after `timeout`.
+#[cfg(feature = "tokio")]
+pub fn read_all(
+    paths: &[String],
+    timeout: Option<Duration>,
+    tx: mpsc::Sender<(usize, Prefetched)>,
+    done: &AtomicBool,
+) {
+    use std::sync::Arc;
+    use tokio::sync::Semaphore;
+    use tokio::task::JoinSet;
+
+    let runtime = match tokio::runtime::Builder::new_multi_thread().build() {
+        Ok(runtime) => runtime,
+        Err(e) => panic!("{e}"),
+    };
+    runtime.block_on(async {
+        let permits = Arc::new(Semaphore::new(IN_FLIGHT));
+        let mut reads = JoinSet::new();
+        for (i, path) in paths.iter().enumerate() {
+            let Ok(permit) = permits.clone().acquire_owned().await else {
+                break;
+            };
+            if done.load(Ordering::Relaxed) {
+                break;
+            }
+            let path = path.clone();
+            let tx = tx.clone();
+            reads.spawn(async move {
+                let contents = match crate::is_local(&path) {
+                    true => read_local(&path).await,
+                    false => {
+                        tokio::task::spawn_blocking(move || crate::read_remote(&path, timeout))
+                            .await
+                            .unwrap_or_else(|e| Err(io::Error::other(e)))
+                            .map(Some)
+                    }
+                };
+                let prefetched = Prefetched {
+                    contents,
+                    _permit: permit,
+                };
+                // The search may already have stopped.
+                let _ = tx.send((i, prefetched));
+            });
+        }
+        while reads.join_next().await.is_some() {}
+    });
+}
+
+/// Reads the local file at `path`, unless it is larger than `MAX_SIZE`.
+#[cfg(feature = "tokio")]
+async fn read_local(path: &str) -> io::Result<Option<Vec<u8>>> {
+    if tokio::fs::metadata(path).await?.len() > MAX_SIZE {
+        return Ok(None);
+    }
+    tokio::fs::read(path).await.map(Some)
+}
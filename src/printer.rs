@@ -0,0 +1,236 @@
+//! Printing of search results.
+
+use crate::json;
+use grep::interval::Interval;
+use regex::Regex;
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+/// A line selected for printing, either because it matched or because it is
+/// part of the context surrounding a match.
+pub struct Line<'a> {
+    pub number: usize,
+    /// Offset in bytes of the start of the line in the file, or in its
+    /// decoded text.
+    pub offset: usize,
+    pub text: Cow<'a, str>,
+    pub matched: bool,
+    /// Whether the line ends with a newline in the file, which only the last
+    /// line of a file can lack.
+    pub terminated: bool,
+    /// Whether the line is only printed as the header of the function or
+    /// section containing the lines after it.
+    pub header: bool,
+}
+
+impl Line<'_> {
+    /// Copies the text of the line if it is borrowed.
+    pub fn into_owned(self) -> Line<'static> {
+        Line {
+            number: self.number,
+            offset: self.offset,
+            text: Cow::Owned(self.text.into_owned()),
+            matched: self.matched,
+            terminated: self.terminated,
+            header: self.header,
+        }
+    }
+}
+
+/// Colors the matches in matching lines, or only some of their capture
+/// groups.
+pub struct Highlighter<'a> {
+    pub re: &'a Regex,
+    /// Capture groups to color instead of the whole match.
+    pub groups: &'a [usize],
+}
+
+impl Highlighter<'_> {
+    const START: &'static str = "\x1b[1;31m";
+    const END: &'static str = "\x1b[0m";
+
+    fn highlight<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        let mut spans: Vec<(usize, usize)> = if self.groups.is_empty() {
+            self.re
+                .find_iter(text)
+                .map(|m| (m.start(), m.end()))
+                .collect()
+        } else {
+            self.re
+                .captures_iter(text)
+                .flat_map(|caps| {
+                    self.groups
+                        .iter()
+                        .filter_map(|&group| caps.get(group))
+                        .map(|m| (m.start(), m.end()))
+                        .collect::<Vec<_>>()
+                })
+                .collect()
+        };
+        spans.retain(|(start, end)| start < end);
+        if spans.is_empty() {
+            return Cow::Borrowed(text);
+        }
+        spans.sort_unstable();
+
+        let mut highlighted = String::with_capacity(text.len() + spans.len() * 11);
+        let mut pos = 0;
+        for (start, end) in spans {
+            // Nested or overlapping groups are colored as one span.
+            if end <= pos {
+                continue;
+            }
+            let start = start.max(pos);
+            highlighted.push_str(&text[pos..start]);
+            highlighted.push_str(Self::START);
+            highlighted.push_str(&text[start..end]);
+            highlighted.push_str(Self::END);
+            pos = end;
+        }
+        highlighted.push_str(&text[pos..]);
+        Cow::Owned(highlighted)
+    }
+}
+
+/// The width of the gutter line numbers are right-aligned in.
+#[derive(Clone, Copy)]
+pub enum Width {
+    /// A fixed number of columns.
+    Fixed(usize),
+    /// As many columns as the largest number printed for a file needs.
+    Auto,
+}
+
+impl Width {
+    /// Parses either a number of columns or `auto`.
+    pub fn parse(s: &str) -> Result<Width, String> {
+        match s {
+            "auto" => Ok(Width::Auto),
+            s => s
+                .parse()
+                .map(Width::Fixed)
+                .map_err(|_| format!("expected a number of columns or auto, found '{s}'")),
+        }
+    }
+}
+
+/// What is printed before the text of each line.
+pub struct Gutter<'a> {
+    pub line_number: bool,
+    pub byte_offset: bool,
+    /// The width line numbers are right-aligned to, if any.
+    pub width: Option<Width>,
+    /// Separates the number or offset of a line from its text.
+    pub separator: &'a str,
+}
+
+/// Prints `lines` as plain text, each preceded by `prefix` and, as set by the
+/// `gutter`, its line number and byte offset, separated from the text by the
+/// separator of the gutter, or by `= ` for headers, and followed by
+/// `terminator`, unless it was not terminated in the file either. Matches are
+/// colored when a `highlighter` is given. With `blocks`, a `--` line
+/// separates the blocks of context whose lines do not follow one another.
+/// The lines are assembled in memory and written all at once, rather than
+/// with a write per line.
+pub fn print_text(
+    out: &mut impl Write,
+    prefix: &str,
+    gutter: &Gutter,
+    lines: &[Line],
+    highlighter: Option<&Highlighter>,
+    terminator: char,
+    blocks: bool,
+) -> io::Result<()> {
+    let size = lines
+        .iter()
+        .map(|line| prefix.len() + line.text.len() + 16)
+        .sum();
+    let width = match gutter.width {
+        Some(Width::Fixed(width)) => width,
+        Some(Width::Auto) => lines
+            .iter()
+            .map(|line| line.number.to_string().len())
+            .max()
+            .unwrap_or_default(),
+        None => 0,
+    };
+    let mut buf = String::with_capacity(size);
+    // The line printed before, unless it was a header, which belongs with
+    // the block after it.
+    let mut previous: Option<&Line> = None;
+    for line in lines {
+        let current = Interval::new(line.number, line.number);
+        let apart = previous.is_some_and(|previous| {
+            Interval::new(previous.number, previous.number)
+                .gap(&current)
+                .is_some()
+        });
+        if blocks && apart {
+            buf.push_str("--");
+            buf.push(terminator);
+        }
+        previous = (!line.header).then_some(line);
+
+        let text = match highlighter {
+            Some(highlighter) if line.matched => highlighter.highlight(&line.text),
+            _ => Cow::Borrowed(line.text.as_ref()),
+        };
+        // Writing to a string cannot fail.
+        buf.push_str(prefix);
+        match (gutter.line_number, gutter.byte_offset) {
+            (true, true) => _ = write!(buf, "{:>width$}:{}", line.number, line.offset),
+            (true, false) => _ = write!(buf, "{:>width$}", line.number),
+            (false, true) => _ = write!(buf, "{}", line.offset),
+            (false, false) => {}
+        }
+        // Headers stand out from the lines selected, even without numbers.
+        match line.header {
+            true => buf.push_str("= "),
+            false if gutter.line_number || gutter.byte_offset => buf.push_str(gutter.separator),
+            false => {}
+        }
+        buf.push_str(&text);
+        if line.terminated {
+            buf.push(terminator);
+        }
+    }
+
+    out.write_all(buf.as_bytes())
+}
+
+/// Prints the name of `path` on a line of its own, above the lines of the
+/// file. When `separate` is set, an empty line separates it from the lines of
+/// the previous file.
+pub fn print_heading(
+    out: &mut impl Write,
+    path: &str,
+    separate: bool,
+    color: bool,
+) -> io::Result<()> {
+    if separate {
+        writeln!(out)?;
+    }
+    if color {
+        writeln!(out, "\x1b[1;35m{path}\x1b[0m")
+    } else {
+        writeln!(out, "{path}")
+    }
+}
+
+/// Prints `lines` as JSON Lines events, starting with the `begin` event for
+/// `path`. The `end` event is left to the caller since it carries statistics
+/// that include the time spent printing.
+pub fn print_json(out: &mut impl Write, path: &str, re: &Regex, lines: &[Line]) -> io::Result<()> {
+    json::begin(out, path)?;
+
+    for line in lines {
+        if line.matched {
+            json::matched(out, path, line.number, &line.text, line.terminated, re)?;
+        } else {
+            json::context(out, path, line.number, &line.text, line.terminated)?;
+        }
+    }
+
+    Ok(())
+}
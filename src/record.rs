@@ -0,0 +1,67 @@
+//! Matching of multi-line records, such as log entries followed by a stack
+//! trace.
+//!
+//! A record starts at every line matching the separator, or with paragraphs,
+//! at every line following a blank line, and runs up to the next one. The
+//! pattern is matched against the text of each record, its lines joined by
+//! newlines, so that a match may span several of them.
+
+use regex::Regex;
+
+/// Flags the `lines` that start a record, which are the first line and those
+/// matching `separator`.
+pub fn starts(separator: &Regex, lines: &[String]) -> Vec<bool> {
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| i == 0 || separator.is_match(line))
+        .collect()
+}
+
+/// Flags the `lines` that start a paragraph, which are the first line and
+/// those following a blank line. Each blank line is a record of its own, so
+/// that paragraphs are printed without the blank lines around them.
+pub fn paragraphs(lines: &[String]) -> Vec<bool> {
+    let blank = |line: &String| line.trim().is_empty();
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| i == 0 || blank(line) || blank(&lines[i - 1]))
+        .collect()
+}
+
+/// Determines which of `lines` hold part of a match of `re` within the
+/// record containing them, where a record starts at every line flagged in
+/// `starts`. Returns a flag per line along with the total number of matches.
+pub fn match_records(re: &Regex, lines: &[String], starts: &[bool]) -> (Vec<bool>, usize) {
+    let mut matched = vec![false; lines.len()];
+    let mut matches = 0;
+    let mut start = 0;
+    while start < lines.len() {
+        let end = (start + 1..lines.len())
+            .find(|&i| starts[i])
+            .unwrap_or(lines.len());
+        let record = &lines[start..end];
+        let text = record.join("\n");
+
+        // The offset in `text` at which each line of the record ends.
+        let ends: Vec<usize> = record
+            .iter()
+            .scan(0, |offset, line| {
+                let end = *offset + line.len();
+                *offset = end + 1;
+                Some(end)
+            })
+            .collect();
+        for m in re.find_iter(&text) {
+            matches += 1;
+            let first = ends.partition_point(|&end| end < m.start());
+            let last = ends.partition_point(|&end| end < m.end());
+            for flag in &mut matched[start + first..=start + last] {
+                *flag = true;
+            }
+        }
+        start = end;
+    }
+    (matched, matches)
+}
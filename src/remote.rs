@@ -0,0 +1,60 @@
+//! Fetching of remote files given by an `http://` or `https://` URL.
+//!
+//! The body of the response is searched like the contents of a local file,
+//! once it has been downloaded in full. A body sent with gzip content
+//! encoding is decompressed as it arrives. A download taking longer than
+//! --timeout fails. It is enabled by the `http` cargo feature.
+
+use std::io;
+#[cfg(feature = "http")]
+use std::io::Read;
+use std::time::Duration;
+
+/// Returns whether `path` is the URL of a remote file.
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// Downloads the file at `url`, giving up after `timeout`. Responses with an
+/// error status fail.
+#[cfg(feature = "http")]
+pub fn fetch(url: &str, timeout: Option<Duration>) -> io::Result<Vec<u8>> {
+    let mut agent = ureq::AgentBuilder::new();
+    if let Some(timeout) = timeout {
+        agent = agent.timeout(timeout);
+    }
+    let response = agent.build().get(url).call().map_err(error)?;
+    let mut bytes = Vec::new();
+    response.into_reader().read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Converts the failure of a request to an error, leaving out the URL that
+/// the message about the file already starts with.
+#[cfg(feature = "http")]
+pub fn error(e: ureq::Error) -> io::Error {
+    use std::error::Error;
+
+    let message = match e {
+        ureq::Error::Status(code, response) => format!("{code} {}", response.status_text()),
+        ureq::Error::Transport(transport) => {
+            let mut message = transport.kind().to_string();
+            if let Some(detail) = transport.message() {
+                message.push_str(&format!(": {detail}"));
+            }
+            if let Some(source) = transport.source() {
+                message.push_str(&format!(": {source}"));
+            }
+            message
+        }
+    };
+    io::Error::other(message)
+}
+
+#[cfg(not(feature = "http"))]
+pub fn fetch(_url: &str, _timeout: Option<Duration>) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "searching URLs requires the http feature",
+    ))
+}
@@ -0,0 +1,250 @@
+//! Replacement templates.
+//!
+//! A template is literal text in which `$N` and `$name` (or `${N}` and
+//! `${name}`) refer to capture groups and `$$` is a literal dollar sign. The
+//! following extensions are also supported:
+//!
+//! - `${N:-default}` expands to `default` when group `N` is empty or did not
+//!   participate in the match.
+//! - `\U` and `\L` convert the rest of the expansion, or everything up to the
+//!   next `\E`, to upper or lower case.
+//!
+//! `\\` is a literal backslash, so that `\\U` stands for the text `\U`. Any
+//! other backslash is kept as it is.
+
+use regex::{Captures, Regex};
+
+#[derive(Clone)]
+enum Group {
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Clone, Copy)]
+enum Case {
+    Upper,
+    Lower,
+    Unchanged,
+}
+
+#[derive(Clone)]
+enum Part {
+    Literal(String),
+    Group {
+        group: Group,
+        default: Option<String>,
+    },
+    Case(Case),
+}
+
+/// A parsed replacement template.
+#[derive(Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// Parses a replacement template.
+    pub fn parse(s: &str) -> Result<Template, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut rest = s;
+
+        while let Some(c) = rest.chars().next() {
+            rest = &rest[c.len_utf8()..];
+            let part = match (c, rest.chars().next()) {
+                ('\\', Some(case @ ('U' | 'L' | 'E'))) => {
+                    rest = &rest[1..];
+                    Part::Case(match case {
+                        'U' => Case::Upper,
+                        'L' => Case::Lower,
+                        _ => Case::Unchanged,
+                    })
+                }
+                ('\\', Some('\\')) => {
+                    rest = &rest[1..];
+                    literal.push('\\');
+                    continue;
+                }
+                ('$', Some('$')) => {
+                    rest = &rest[1..];
+                    literal.push('$');
+                    continue;
+                }
+                ('$', Some('{')) => {
+                    let end = rest
+                        .find('}')
+                        .ok_or_else(|| format!("unclosed '${{' in template '{s}'"))?;
+                    let reference = &rest[1..end];
+                    rest = &rest[end + 1..];
+
+                    let (name, default) = match reference.split_once(":-") {
+                        Some((name, default)) => (name, Some(default.to_string())),
+                        None => (reference, None),
+                    };
+                    Part::Group {
+                        group: group(name)
+                            .ok_or_else(|| format!("invalid group '{name}' in template '{s}'"))?,
+                        default,
+                    }
+                }
+                ('$', Some(_)) => {
+                    let end = rest
+                        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                        .unwrap_or(rest.len());
+                    let Some(group) = group(&rest[..end]) else {
+                        literal.push('$');
+                        continue;
+                    };
+                    rest = &rest[end..];
+                    Part::Group {
+                        group,
+                        default: None,
+                    }
+                }
+                (c, _) => {
+                    literal.push(c);
+                    continue;
+                }
+            };
+
+            if !literal.is_empty() {
+                parts.push(Part::Literal(std::mem::take(&mut literal)));
+            }
+            parts.push(part);
+        }
+
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Template { parts })
+    }
+
+    /// Appends the expansion of the template for `caps` to `dst`.
+    pub fn expand(&self, caps: &Captures, dst: &mut String) {
+        let mut case = Case::Unchanged;
+        for part in &self.parts {
+            let text = match part {
+                Part::Literal(literal) => literal.as_str(),
+                Part::Group { group, default } => {
+                    let m = match group {
+                        Group::Index(i) => caps.get(*i),
+                        Group::Name(name) => caps.name(name),
+                    };
+                    match (m.map(|m| m.as_str()), default) {
+                        (Some(""), Some(default)) | (None, Some(default)) => default.as_str(),
+                        (m, _) => m.unwrap_or_default(),
+                    }
+                }
+                Part::Case(c) => {
+                    case = *c;
+                    continue;
+                }
+            };
+
+            match case {
+                Case::Upper => dst.push_str(&text.to_uppercase()),
+                Case::Lower => dst.push_str(&text.to_lowercase()),
+                Case::Unchanged => dst.push_str(text),
+            }
+        }
+    }
+
+    /// Replaces every match of `re` in `text` with the expansion of the
+    /// template.
+    pub fn replace_all(&self, re: &Regex, text: &str) -> String {
+        let mut replaced = String::with_capacity(text.len());
+        let mut pos = 0;
+        for caps in re.captures_iter(text) {
+            let m = caps.get(0).unwrap();
+            replaced.push_str(&text[pos..m.start()]);
+            self.expand(&caps, &mut replaced);
+            pos = m.end();
+        }
+        replaced.push_str(&text[pos..]);
+        replaced
+    }
+}
+
+/// Parses a reference to a capture group by index or by name.
+fn group(name: &str) -> Option<Group> {
+    if name.is_empty() {
+        return None;
+    }
+
+    Some(match name.parse() {
+        Ok(i) => Group::Index(i),
+        Err(_) => Group::Name(name.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replaces the matches of `re` in `text` with `template`.
+    fn replace(template: &str, re: &str, text: &str) -> String {
+        let template = Template::parse(template).unwrap();
+        template.replace_all(&Regex::new(re).unwrap(), text)
+    }
+
+    #[test]
+    fn groups() {
+        assert_eq!(
+            replace("$2 $1", r"(\w+) (\w+)", "hello world"),
+            "world hello"
+        );
+        assert_eq!(replace("${1}x", r"(\w+)", "a b"), "ax bx");
+        assert_eq!(replace("$0$0", "a", "ab"), "aab");
+        assert_eq!(
+            replace(
+                "$last, ${first}",
+                r"(?<first>\w+) (?<last>\w+)",
+                "Ada Lovelace"
+            ),
+            "Lovelace, Ada"
+        );
+        // A group that did not participate, or that does not exist, is empty.
+        assert_eq!(replace("[$2]", "(a)|(b)", "a"), "[]");
+        assert_eq!(replace("[$9][$nope]", "a", "a"), "[][]");
+    }
+
+    #[test]
+    fn defaults() {
+        assert_eq!(replace("${2:-none}", "(a)(b)?", "a"), "none");
+        assert_eq!(replace("${2:-none}", "(a)(b?)", "a"), "none");
+        assert_eq!(replace("${2:-none}", "(a)(b)?", "ab"), "b");
+        assert_eq!(replace("${x:-}", "(?<x>a)?b", "b"), "");
+    }
+
+    #[test]
+    fn literal_dollars() {
+        assert_eq!(replace("$$1", "a", "a"), "$1");
+        assert_eq!(replace("costs $", "a", "a"), "costs $");
+        assert_eq!(replace("$ $.", "a", "a"), "$ $.");
+    }
+
+    #[test]
+    fn case_changes() {
+        assert_eq!(replace(r"\U$1", r"(\w+)", "abc"), "ABC");
+        assert_eq!(replace(r"\L$1!", r"(\w+)", "ABC"), "abc!");
+        assert_eq!(replace(r"\U$1\E-$2", r"(\w+) (\w+)", "ab cd"), "AB-cd");
+        assert_eq!(replace(r"x\Uy\Lz$1", "(A)", "A"), "xYza");
+    }
+
+    #[test]
+    fn backslashes() {
+        assert_eq!(replace(r"\\U$1", "(a)", "a"), r"\Ua");
+        assert_eq!(replace(r"a\\b", "x", "x"), r"a\b");
+        assert_eq!(replace(r"\n\t", "x", "x"), r"\n\t");
+        assert_eq!(replace("end\\", "x", "x"), "end\\");
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        for template in ["${1", "${", "${}", "${:-x}", "a ${name"] {
+            assert!(Template::parse(template).is_err(), "{template}");
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//! Output for the grep-mode of Emacs, which visits each match on a `RET`.
+//! A mode line opens the output, matching lines are printed as
+//! `file:line:column: text` and their context as `file-line-text`, and a
+//! closing line tells whether any match was found, as `M-x grep` does.
+
+use super::column;
+use crate::printer::Line;
+use regex::Regex;
+use std::env;
+use std::io::{self, Write};
+
+pub fn begin(out: &mut impl Write) -> io::Result<()> {
+    // Paths are relative to the directory the search ran in.
+    let directory = env::current_dir()?;
+    let directory = directory.to_string_lossy();
+    let directory = directory.trim_end_matches('/');
+    writeln!(
+        out,
+        r#"-*- mode: grep; default-directory: "{}/" -*-"#,
+        directory.replace('\\', r"\\").replace('"', r#"\""#)
+    )?;
+    writeln!(out)
+}
+
+/// Writes the `lines` of the file at `path`, and returns the number of
+/// matching lines among them.
+pub fn file(out: &mut impl Write, path: &str, re: &Regex, lines: &[Line]) -> io::Result<usize> {
+    let mut matched = 0;
+    for line in lines {
+        match re.find(&line.text).filter(|_| line.matched) {
+            Some(m) => {
+                let column = column(&line.text, m.start());
+                writeln!(out, "{path}:{}:{column}: {}", line.number, line.text)?;
+                matched += 1;
+            }
+            None => writeln!(out, "{path}-{}-{}", line.number, line.text)?,
+        }
+    }
+    Ok(matched)
+}
+
+pub fn end(out: &mut impl Write, results: usize) -> io::Result<()> {
+    let outcome = match results {
+        0 => "no matches found",
+        _ => "matches found",
+    };
+    writeln!(out)?;
+    writeln!(out, "Grep finished with {outcome}")
+}
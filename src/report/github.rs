@@ -0,0 +1,37 @@
+//! GitHub Actions workflow commands, annotating every match as a warning on
+//! its file, line and columns.
+
+use super::column;
+use crate::printer::Line;
+use regex::Regex;
+use std::io::{self, Write};
+
+pub fn file(out: &mut impl Write, path: &str, re: &Regex, lines: &[Line]) -> io::Result<()> {
+    for line in lines.iter().filter(|line| line.matched) {
+        for m in re.find_iter(&line.text) {
+            writeln!(
+                out,
+                "::warning file={},line={},col={},endColumn={},title={}::{}",
+                escape_property(path),
+                line.number,
+                column(&line.text, m.start()),
+                column(&line.text, m.end()),
+                escape_property(&format!("Match for '{}'", re.as_str())),
+                escape_data(&line.text)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Escapes the message of a workflow command.
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escapes the value of a property of a workflow command.
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
@@ -0,0 +1,88 @@
+//! Matches grouped by the value of a capture group, as a summary of a log.
+//! Each group is written once the search is complete with its number of
+//! matches and, when requested, the first and last lines matching in it. The
+//! largest groups come first, and groups as large in the order they were
+//! found.
+
+use crate::printer::Line;
+use regex::Regex;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// The matches in a group seen so far.
+struct Group {
+    /// The order in which the group was found.
+    index: usize,
+    matches: usize,
+    /// The first and last lines matching in the group, prefixed with their
+    /// file and line number.
+    first: String,
+    last: String,
+}
+
+/// The groups seen so far.
+pub struct Groups {
+    /// The capture group, given by number or name.
+    group: String,
+    /// Whether the first and last lines of each group are written.
+    lines: bool,
+    groups: HashMap<String, Group>,
+}
+
+impl Groups {
+    pub fn new(group: String, lines: bool) -> Groups {
+        Groups {
+            group,
+            lines,
+            groups: HashMap::new(),
+        }
+    }
+
+    /// Adds the matches of `re` on the matching `lines` of the file at
+    /// `path` to their groups. Matches in which the group does not take part
+    /// are ignored.
+    pub fn file(&mut self, path: &str, re: &Regex, lines: &[Line]) {
+        for line in lines.iter().filter(|line| line.matched) {
+            for caps in re.captures_iter(&line.text) {
+                let Some(m) = super::capture(&caps, &self.group) else {
+                    continue;
+                };
+                let text = match self.lines {
+                    true => format!("{path}:{}: {}", line.number, line.text),
+                    false => String::new(),
+                };
+                let index = self.groups.len();
+                let group = self
+                    .groups
+                    .entry(m.as_str().to_string())
+                    .or_insert_with(|| Group {
+                        index,
+                        matches: 0,
+                        first: text.clone(),
+                        last: String::new(),
+                    });
+                group.matches += 1;
+                group.last = text;
+            }
+        }
+    }
+
+    /// Writes each group with its number of matches, followed by its first
+    /// and last lines if requested.
+    pub fn end(self, out: &mut impl Write) -> io::Result<()> {
+        let mut groups: Vec<_> = self.groups.into_iter().collect();
+        groups.sort_by(|(_, a), (_, b)| b.matches.cmp(&a.matches).then(a.index.cmp(&b.index)));
+
+        for (value, group) in groups {
+            match group.matches {
+                1 => writeln!(out, "{value}: 1 match")?,
+                n => writeln!(out, "{value}: {n} matches")?,
+            }
+            if self.lines {
+                writeln!(out, "  first: {}", group.first)?;
+                writeln!(out, "  last:  {}", group.last)?;
+            }
+        }
+        Ok(())
+    }
+}
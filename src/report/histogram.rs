@@ -0,0 +1,52 @@
+//! A histogram of the values of a capture group. Rather than writing the
+//! results of each file, it tallies the distinct values the group takes in
+//! every match and writes their counts once the search is complete, the most
+//! frequent first, like `sort | uniq -c | sort -rn`.
+
+use crate::printer::Line;
+use regex::Regex;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// The counts of the values of a capture group seen so far.
+pub struct Histogram {
+    /// The capture group, given by number or name.
+    group: String,
+    counts: HashMap<String, usize>,
+}
+
+impl Histogram {
+    pub fn new(group: String) -> Histogram {
+        Histogram {
+            group,
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Counts the values of the group in the matches of `re` on the matching
+    /// `lines`. Matches in which the group does not take part are ignored.
+    pub fn file(&mut self, re: &Regex, lines: &[Line]) {
+        for line in lines.iter().filter(|line| line.matched) {
+            for caps in re.captures_iter(&line.text) {
+                if let Some(m) = super::capture(&caps, &self.group) {
+                    *self.counts.entry(m.as_str().to_string()).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    /// Writes each value with its count, the most frequent first and values
+    /// as frequent in order.
+    pub fn end(self, out: &mut impl Write) -> io::Result<()> {
+        let mut counts: Vec<_> = self.counts.into_iter().collect();
+        counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+
+        let width = counts
+            .first()
+            .map_or(0, |(_, count)| count.to_string().len());
+        for (value, count) in counts {
+            writeln!(out, "{count:>width$} {value}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+//! A standalone HTML page, with a collapsible section for every file that
+//! lists its matching lines with the matches highlighted. Context lines can
+//! be hidden with a checkbox at the top of the page.
+
+use super::escape_xml;
+use crate::printer::Line;
+use regex::Regex;
+use std::io::{self, Write};
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em; }
+pre { background: #f6f8fa; padding: 0.5em; overflow-x: auto; }
+.number { color: #888; user-select: none; }
+.context { color: #666; }
+mark { background: #ffd33d; }
+#context:not(:checked) ~ main .context { display: none; }";
+
+pub fn begin(out: &mut impl Write, re: &Regex) -> io::Result<()> {
+    let title = escape_xml(&format!("Matches for '{}'", re.as_str()));
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, r#"<html lang="en">"#)?;
+    writeln!(out, r#"<head><meta charset="utf-8"><title>{title}</title>"#)?;
+    writeln!(out, "<style>\n{STYLE}\n</style></head>")?;
+    writeln!(out, "<body>\n<h1>{title}</h1>")?;
+    writeln!(
+        out,
+        r#"<input type="checkbox" id="context" checked><label for="context">Show context</label>"#
+    )?;
+    writeln!(out, "<main>")
+}
+
+pub fn file(out: &mut impl Write, path: &str, re: &Regex, lines: &[Line]) -> io::Result<()> {
+    if lines.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(
+        out,
+        "<details open><summary>{} ({})</summary>\n<pre>",
+        escape_xml(path),
+        super::matching_lines(lines)
+    )?;
+    for line in lines {
+        let (class, text) = if line.matched {
+            ("match", highlight(re, &line.text))
+        } else {
+            ("context", escape_xml(&line.text))
+        };
+        // The newline is part of the line so that hidden context leaves no
+        // empty lines behind.
+        write!(
+            out,
+            "<span class=\"{class}\"><span class=\"number\">{:>6}: </span>{text}\n</span>",
+            line.number
+        )?;
+    }
+    writeln!(out, "</pre></details>")
+}
+
+pub fn end(out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "</main>\n</body>\n</html>")
+}
+
+/// Escapes `text`, wrapping every match of `re` in a `mark` element.
+fn highlight(re: &Regex, text: &str) -> String {
+    let mut highlighted = String::with_capacity(text.len());
+    let mut pos = 0;
+    for m in re.find_iter(text).filter(|m| !m.is_empty()) {
+        highlighted.push_str(&escape_xml(&text[pos..m.start()]));
+        highlighted.push_str("<mark>");
+        highlighted.push_str(&escape_xml(m.as_str()));
+        highlighted.push_str("</mark>");
+        pos = m.end();
+    }
+    highlighted.push_str(&escape_xml(&text[pos..]));
+    highlighted
+}
@@ -0,0 +1,47 @@
+//! JUnit XML output. Every file is a test suite in which every matching line
+//! is a failed test case, and a file without matches has a single passing
+//! test case.
+
+use super::escape_xml;
+use crate::printer::Line;
+use regex::Regex;
+use std::io::{self, Write};
+
+pub fn begin(out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(out, r#"<testsuites name="{}">"#, env!("CARGO_PKG_NAME"))
+}
+
+pub fn file(out: &mut impl Write, path: &str, re: &Regex, lines: &[Line]) -> io::Result<()> {
+    let path = escape_xml(path);
+    let failures = lines.iter().filter(|line| line.matched).count();
+    writeln!(
+        out,
+        r#"  <testsuite name="{path}" tests="{}" failures="{failures}">"#,
+        failures.max(1)
+    )?;
+
+    if failures == 0 {
+        writeln!(out, r#"    <testcase name="{path}" classname="{path}"/>"#)?;
+    }
+    let message = escape_xml(&format!("Line matches '{}'", re.as_str()));
+    for line in lines.iter().filter(|line| line.matched) {
+        writeln!(
+            out,
+            r#"    <testcase name="{path}:{}" classname="{path}">"#,
+            line.number
+        )?;
+        writeln!(
+            out,
+            r#"      <failure message="{message}">{}</failure>"#,
+            escape_xml(&line.text)
+        )?;
+        writeln!(out, "    </testcase>")?;
+    }
+
+    writeln!(out, "  </testsuite>")
+}
+
+pub fn end(out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "</testsuites>")
+}
@@ -0,0 +1,55 @@
+//! Markdown output, with a heading for every file followed by a fenced code
+//! block of its matching lines and their context. As in the output of grep,
+//! line numbers are followed by `:` on matching lines and by `-` on context
+//! lines.
+
+use crate::printer::Line;
+use regex::Regex;
+use std::io::{self, Write};
+
+pub fn begin(out: &mut impl Write, re: &Regex) -> io::Result<()> {
+    writeln!(out, "# Matches for {}", code(re.as_str()))
+}
+
+pub fn file(out: &mut impl Write, path: &str, lines: &[Line]) -> io::Result<()> {
+    if lines.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out, "\n## {}\n", code(path))?;
+    writeln!(out, "{}\n", super::matching_lines(lines))?;
+
+    // The fence must be longer than any run of backticks in the lines.
+    let fence = "`".repeat(
+        lines
+            .iter()
+            .map(|line| longest_backtick_run(&line.text) + 1)
+            .max()
+            .unwrap_or_default()
+            .max(3),
+    );
+    writeln!(out, "{fence}")?;
+    for line in lines {
+        let separator = if line.matched { ':' } else { '-' };
+        writeln!(out, "{}{separator} {}", line.number, line.text)?;
+    }
+    writeln!(out, "{fence}")
+}
+
+/// Formats `s` as inline code.
+fn code(s: &str) -> String {
+    let delimiter = "`".repeat(longest_backtick_run(s) + 1);
+    // Padding keeps backticks at either end from merging with the delimiter.
+    if s.starts_with('`') || s.ends_with('`') {
+        format!("{delimiter} {s} {delimiter}")
+    } else {
+        format!("{delimiter}{s}{delimiter}")
+    }
+}
+
+fn longest_backtick_run(s: &str) -> usize {
+    s.split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default()
+}
@@ -0,0 +1,186 @@
+//! Reports in formats understood by other tools, selected with
+//! `--output-format`.
+//!
+//! A report is written as the search goes: a header once the search starts,
+//! the results of each file as soon as it has been searched, and a footer
+//! once every file has been searched. Histograms and groups are written
+//! only at the end, aggregating the results of every file, and so are the
+//! matches chosen with --select.
+
+mod emacs;
+mod github;
+mod groups;
+mod histogram;
+mod html;
+mod junit;
+mod markdown;
+mod sarif;
+mod select;
+
+use crate::printer::Line;
+use clap::ValueEnum;
+use groups::Groups;
+use histogram::Histogram;
+use regex::{Captures, Match, Regex};
+use select::Selection;
+use std::io::{self, Write};
+
+/// The format of the output.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Matching lines as plain text.
+    Text,
+    /// A SARIF 2.1.0 log, as uploaded to code scanning services.
+    Sarif,
+    /// GitHub Actions annotations, one warning per match.
+    Github,
+    /// A JUnit XML report, with a failed test case per matching line.
+    Junit,
+    /// A standalone HTML page with the matches highlighted.
+    Html,
+    /// A Markdown summary with a section per file.
+    Markdown,
+    /// Matching lines as `file:line:column: text`, for the grep-mode of
+    /// Emacs.
+    Emacs,
+}
+
+/// A report being written.
+pub enum Report {
+    /// The results of each file written in `format` as it is searched, with
+    /// the number of results written so far.
+    Results {
+        format: OutputFormat,
+        results: usize,
+    },
+    /// The histogram written in place of the results, with --histogram.
+    Histogram(Histogram),
+    /// The groups written in place of the results, with --group-by.
+    Groups(Groups),
+    /// The matches to choose from in place of the results, with --select.
+    Selection(Selection),
+}
+
+impl Report {
+    /// Starts a report on the search for `re`, writing its header to `out`.
+    pub fn begin(out: &mut impl Write, format: OutputFormat, re: &Regex) -> io::Result<Report> {
+        match format {
+            OutputFormat::Text | OutputFormat::Github => {}
+            OutputFormat::Sarif => sarif::begin(out, re)?,
+            OutputFormat::Junit => junit::begin(out)?,
+            OutputFormat::Html => html::begin(out, re)?,
+            OutputFormat::Markdown => markdown::begin(out, re)?,
+            OutputFormat::Emacs => emacs::begin(out)?,
+        }
+        Ok(Report::Results { format, results: 0 })
+    }
+
+    /// Starts a histogram of the values of the capture `group`, given by
+    /// number or name, in every match.
+    pub fn histogram(group: String) -> Report {
+        Report::Histogram(Histogram::new(group))
+    }
+
+    /// Starts grouping the matches by the value of the capture `group`,
+    /// given by number or name, along with the first and last line of each
+    /// group when `lines` is set.
+    pub fn groups(group: String, lines: bool) -> Report {
+        Report::Groups(Groups::new(group, lines))
+    }
+
+    /// Starts gathering the matches to choose from on the terminal.
+    pub fn selection() -> Report {
+        Report::Selection(Selection::new())
+    }
+
+    /// Writes the selected `lines` of the file at `path` to the report.
+    pub fn file(
+        &mut self,
+        out: &mut impl Write,
+        path: &str,
+        re: &Regex,
+        lines: &[Line],
+    ) -> io::Result<()> {
+        let (format, results) = match self {
+            Report::Results { format, results } => (*format, results),
+            Report::Histogram(histogram) => {
+                histogram.file(re, lines);
+                return Ok(());
+            }
+            Report::Groups(groups) => {
+                groups.file(path, re, lines);
+                return Ok(());
+            }
+            Report::Selection(selection) => {
+                selection.file(path, lines);
+                return Ok(());
+            }
+        };
+        match format {
+            OutputFormat::Text => {}
+            OutputFormat::Sarif => *results = sarif::file(out, path, re, lines, *results)?,
+            OutputFormat::Github => github::file(out, path, re, lines)?,
+            OutputFormat::Junit => junit::file(out, path, re, lines)?,
+            OutputFormat::Html => html::file(out, path, re, lines)?,
+            OutputFormat::Markdown => markdown::file(out, path, lines)?,
+            OutputFormat::Emacs => *results += emacs::file(out, path, re, lines)?,
+        }
+        Ok(())
+    }
+
+    /// Finishes the report, writing its footer to `out`.
+    pub fn end(self, out: &mut impl Write) -> io::Result<()> {
+        let (format, results) = match self {
+            Report::Results { format, results } => (format, results),
+            Report::Histogram(histogram) => return histogram.end(out),
+            Report::Groups(groups) => return groups.end(out),
+            Report::Selection(selection) => return selection.end(out),
+        };
+        match format {
+            OutputFormat::Text | OutputFormat::Github | OutputFormat::Markdown => Ok(()),
+            OutputFormat::Sarif => sarif::end(out),
+            OutputFormat::Junit => junit::end(out),
+            OutputFormat::Html => html::end(out),
+            OutputFormat::Emacs => emacs::end(out, results),
+        }
+    }
+}
+
+/// Describes the number of matching lines among `lines`.
+fn matching_lines(lines: &[Line]) -> String {
+    match lines.iter().filter(|line| line.matched).count() {
+        1 => "1 matching line".to_string(),
+        n => format!("{n} matching lines"),
+    }
+}
+
+/// Returns the capture `group`, given by number or name, of a match.
+fn capture<'h>(caps: &Captures<'h>, group: &str) -> Option<Match<'h>> {
+    match group.parse::<usize>() {
+        Ok(group) => caps.get(group),
+        Err(_) => caps.name(group),
+    }
+}
+
+/// Escapes `s` for use in XML text or attribute values.
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters other than whitespace are not allowed in XML.
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => escaped.push('\u{fffd}'),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Returns the 1-based column of the character at byte `offset` of `text`.
+fn column(text: &str, offset: usize) -> usize {
+    text[..offset].chars().count() + 1
+}
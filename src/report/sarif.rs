@@ -0,0 +1,66 @@
+//! SARIF 2.1.0 output. The pattern is the only rule of the run, and every
+//! match is a result located at its line and columns.
+
+use super::column;
+use crate::json::escape;
+use crate::printer::Line;
+use regex::Regex;
+use std::io::{self, Write};
+
+const RULE_ID: &str = "pattern";
+
+pub fn begin(out: &mut impl Write, re: &Regex) -> io::Result<()> {
+    write!(
+        out,
+        concat!(
+            r#"{{"$schema":"https://json.schemastore.org/sarif-2.1.0.json","version":"2.1.0","#,
+            r#""runs":[{{"tool":{{"driver":{{"name":"{}","version":"{}","#,
+            r#""rules":[{{"id":"{}","shortDescription":{{"text":"Lines matching '{}'"}}}}]}}}},"#,
+            r#""columnKind":"unicodeCodePoints","results":["#
+        ),
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        RULE_ID,
+        escape(re.as_str())
+    )
+}
+
+/// Writes a result for every match in `lines`, following the `results`
+/// written before. Returns the number of results written so far.
+pub fn file(
+    out: &mut impl Write,
+    path: &str,
+    re: &Regex,
+    lines: &[Line],
+    mut results: usize,
+) -> io::Result<usize> {
+    for line in lines.iter().filter(|line| line.matched) {
+        for m in re.find_iter(&line.text) {
+            if results > 0 {
+                write!(out, ",")?;
+            }
+            results += 1;
+            write!(
+                out,
+                concat!(
+                    r#"{{"ruleId":"{}","level":"warning","message":{{"text":"Match '{}'"}},"#,
+                    r#""locations":[{{"physicalLocation":{{"artifactLocation":{{"uri":"{}"}},"#,
+                    r#""region":{{"startLine":{},"startColumn":{},"endColumn":{},"#,
+                    r#""snippet":{{"text":"{}"}}}}}}}}]}}"#
+                ),
+                RULE_ID,
+                escape(m.as_str()),
+                escape(path),
+                line.number,
+                column(&line.text, m.start()),
+                column(&line.text, m.end()),
+                escape(&line.text)
+            )?;
+        }
+    }
+    Ok(results)
+}
+
+pub fn end(out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "]}}]}}")
+}
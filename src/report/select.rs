@@ -0,0 +1,368 @@
+//! An interactive picker among the matching lines, for --select. Rather
+//! than writing the results of each file, it gathers the matching lines and
+//! once the search is complete, lets them be narrowed down and chosen on the
+//! terminal, then writes the chosen ones as `file:line`, as taken by
+//! `$EDITOR +line file`.
+//!
+//! The picker is drawn on the controlling terminal rather than on stdout, so
+//! that its output can be captured by the shell. The arrow keys move through
+//! the matches, typing filters them, Tab marks several of them, Enter
+//! chooses the marked matches or else the current one, and Escape or Ctrl-C
+//! cancels. It is only available on Unix.
+
+use crate::printer::Line;
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+/// The matching lines gathered so far.
+pub struct Selection {
+    entries: Vec<Entry>,
+}
+
+/// A matching line that can be chosen.
+struct Entry {
+    path: String,
+    number: usize,
+    /// The entry as shown in the picker, `file:line: text`.
+    label: String,
+    /// The label in lowercase, which filters are matched against.
+    key: String,
+}
+
+impl Selection {
+    pub fn new() -> Selection {
+        Selection {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Gathers the matching `lines` of the file at `path`.
+    pub fn file(&mut self, path: &str, lines: &[Line]) {
+        for line in lines.iter().filter(|line| line.matched) {
+            let label = format!("{path}:{}: {}", line.number, line.text.replace('\t', " "));
+            self.entries.push(Entry {
+                path: path.to_string(),
+                number: line.number,
+                key: label.to_lowercase(),
+                label,
+            });
+        }
+    }
+
+    /// Lets the matching lines be chosen and writes the chosen ones. Fails
+    /// with `io::ErrorKind::Interrupted` when the picker is cancelled.
+    pub fn end(self, out: &mut impl Write) -> io::Result<()> {
+        if self.entries.is_empty() {
+            return Ok(());
+        }
+        let chosen = Picker::new(&self.entries).run(&mut Terminal::open()?)?;
+        for entry in chosen.into_iter().map(|i| &self.entries[i]) {
+            writeln!(out, "{}:{}", entry.path, entry.number)?;
+        }
+        Ok(())
+    }
+}
+
+/// A key pressed in the picker.
+enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Enter,
+    Tab,
+    Backspace,
+    /// Ctrl-U, which clears the filter.
+    Clear,
+    Cancel,
+    Text(String),
+    Other,
+}
+
+/// Parses the keys sent by the terminal in `bytes`. A lone escape is taken
+/// as the Escape key, since escape sequences arrive in a single read.
+fn keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let (key, len) = match bytes[i] {
+            0x1b => match bytes.get(i + 1) {
+                None => (Key::Cancel, 1),
+                Some(b'[' | b'O') => {
+                    // A control sequence ends with a byte from `@` to `~`.
+                    let end = bytes[i + 2..]
+                        .iter()
+                        .position(|b| (0x40..=0x7e).contains(b))
+                        .map_or(bytes.len(), |end| i + 2 + end + 1);
+                    let key = match &bytes[i + 2..end] {
+                        b"A" => Key::Up,
+                        b"B" => Key::Down,
+                        b"5~" => Key::PageUp,
+                        b"6~" => Key::PageDown,
+                        _ => Key::Other,
+                    };
+                    (key, end - i)
+                }
+                Some(_) => (Key::Other, 1),
+            },
+            0x03 | 0x07 => (Key::Cancel, 1),
+            b'\r' | b'\n' => (Key::Enter, 1),
+            b'\t' => (Key::Tab, 1),
+            0x7f | 0x08 => (Key::Backspace, 1),
+            0x10 => (Key::Up, 1),
+            0x0e => (Key::Down, 1),
+            0x15 => (Key::Clear, 1),
+            b if b < 0x20 => (Key::Other, 1),
+            _ => {
+                let len = bytes[i..]
+                    .iter()
+                    .position(|&b| b < 0x20 || b == 0x7f)
+                    .unwrap_or(bytes.len() - i);
+                let text = String::from_utf8_lossy(&bytes[i..i + len]).into_owned();
+                (Key::Text(text), len)
+            }
+        };
+        keys.push(key);
+        i += len;
+    }
+    keys
+}
+
+/// The state of the picker.
+struct Picker<'a> {
+    entries: &'a [Entry],
+    filter: String,
+    /// Indices of the entries containing the filter.
+    shown: Vec<usize>,
+    /// Position of the current entry among those shown.
+    cursor: usize,
+    /// Position of the entry at the top of the screen among those shown.
+    top: usize,
+    /// Indices of the marked entries.
+    marked: BTreeSet<usize>,
+}
+
+impl Picker<'_> {
+    fn new(entries: &[Entry]) -> Picker<'_> {
+        Picker {
+            entries,
+            filter: String::new(),
+            shown: (0..entries.len()).collect(),
+            cursor: 0,
+            top: 0,
+            marked: BTreeSet::new(),
+        }
+    }
+
+    /// Runs the picker on `terminal` until matches are chosen, and returns
+    /// the indices of the chosen entries.
+    fn run(mut self, terminal: &mut Terminal) -> io::Result<Vec<usize>> {
+        let mut buf = [0; 256];
+        loop {
+            self.draw(terminal)?;
+            let n = terminal.read(&mut buf)?;
+            if n == 0 {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            for key in keys(&buf[..n]) {
+                let page = terminal.size().0.saturating_sub(2).max(1);
+                match key {
+                    Key::Up => self.cursor = self.cursor.saturating_sub(1),
+                    Key::Down => self.cursor += 1,
+                    Key::PageUp => self.cursor = self.cursor.saturating_sub(page),
+                    Key::PageDown => self.cursor += page,
+                    Key::Tab => {
+                        if let Some(&i) = self.shown.get(self.cursor) {
+                            if !self.marked.remove(&i) {
+                                self.marked.insert(i);
+                            }
+                            self.cursor += 1;
+                        }
+                    }
+                    Key::Enter if !self.marked.is_empty() => {
+                        return Ok(self.marked.into_iter().collect());
+                    }
+                    Key::Enter => {
+                        if let Some(&i) = self.shown.get(self.cursor) {
+                            return Ok(vec![i]);
+                        }
+                    }
+                    Key::Backspace => {
+                        self.filter.pop();
+                        self.refilter();
+                    }
+                    Key::Clear => {
+                        self.filter.clear();
+                        self.refilter();
+                    }
+                    Key::Text(text) => {
+                        self.filter.push_str(&text);
+                        self.refilter();
+                    }
+                    Key::Cancel => return Err(io::ErrorKind::Interrupted.into()),
+                    Key::Other => {}
+                }
+                self.cursor = self.cursor.min(self.shown.len().saturating_sub(1));
+            }
+        }
+    }
+
+    /// Shows only the entries containing the filter, ignoring case.
+    fn refilter(&mut self) {
+        let filter = self.filter.to_lowercase();
+        self.shown = (0..self.entries.len())
+            .filter(|&i| self.entries[i].key.contains(&filter))
+            .collect();
+        self.cursor = 0;
+        self.top = 0;
+    }
+
+    /// Draws the filter on the first row, as many of the entries shown as
+    /// fit below it with the current one highlighted, and their count on
+    /// the last row.
+    fn draw(&mut self, terminal: &mut Terminal) -> io::Result<()> {
+        let (rows, columns) = terminal.size();
+        let height = rows.saturating_sub(2).max(1);
+        if self.cursor < self.top {
+            self.top = self.cursor;
+        } else if self.cursor >= self.top + height {
+            self.top = self.cursor + 1 - height;
+        }
+
+        let mut screen = Vec::new();
+        write!(screen, "\x1b[H\x1b[2K> {}", self.filter)?;
+        for row in 0..height {
+            write!(screen, "\r\n\x1b[2K")?;
+            let Some(&i) = self.shown.get(self.top + row) else {
+                continue;
+            };
+            let mark = if self.marked.contains(&i) { '*' } else { ' ' };
+            let label: String = self.entries[i]
+                .label
+                .chars()
+                .filter(|c| !c.is_control())
+                .take(columns.saturating_sub(2))
+                .collect();
+            match self.top + row == self.cursor {
+                true => write!(screen, "{mark} \x1b[7m{label}\x1b[0m")?,
+                false => write!(screen, "{mark} {label}")?,
+            }
+        }
+        write!(
+            screen,
+            "\r\n\x1b[2K\x1b[2m{}/{} matches, {} marked\x1b[0m",
+            self.shown.len(),
+            self.entries.len(),
+            self.marked.len()
+        )?;
+        // The cursor is left at the end of the filter.
+        write!(screen, "\x1b[1;{}H", self.filter.chars().count() + 3)?;
+        terminal.write(&screen)
+    }
+}
+
+/// The controlling terminal, switched to its alternate screen with its
+/// input read a key at a time while the picker runs.
+#[cfg(unix)]
+struct Terminal {
+    tty: std::fs::File,
+    /// The settings to restore once the picker is done.
+    saved: libc::termios,
+}
+
+#[cfg(unix)]
+impl Terminal {
+    fn open() -> io::Result<Terminal> {
+        use std::os::fd::AsRawFd;
+
+        let tty = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")?;
+        let mut saved = std::mem::MaybeUninit::<libc::termios>::uninit();
+        // SAFETY: `saved` is only read once `tcgetattr` has filled it in.
+        if unsafe { libc::tcgetattr(tty.as_raw_fd(), saved.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `tcgetattr` succeeded, so it filled in every field.
+        let saved = unsafe { saved.assume_init() };
+
+        let mut raw = saved;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+        raw.c_iflag &= !(libc::ICRNL | libc::IXON);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        // SAFETY: `raw` is a valid `termios` that outlives the call, which
+        // only reads it.
+        if unsafe { libc::tcsetattr(tty.as_raw_fd(), libc::TCSAFLUSH, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut terminal = Terminal { tty, saved };
+        terminal.write(b"\x1b[?1049h")?;
+        Ok(terminal)
+    }
+
+    /// Returns the number of rows and columns of the terminal.
+    fn size(&self) -> (usize, usize) {
+        use std::os::fd::AsRawFd;
+
+        let mut size = std::mem::MaybeUninit::<libc::winsize>::uninit();
+        // SAFETY: TIOCGWINSZ writes a `winsize` through the pointer, which
+        // points to one, and `size` is only read once `ioctl` has filled it in.
+        if unsafe { libc::ioctl(self.tty.as_raw_fd(), libc::TIOCGWINSZ, size.as_mut_ptr()) } != 0 {
+            return (24, 80);
+        }
+        // SAFETY: `ioctl` succeeded, so it filled in every field.
+        let size = unsafe { size.assume_init() };
+        match (size.ws_row, size.ws_col) {
+            (0, _) | (_, 0) => (24, 80),
+            (rows, columns) => (rows as usize, columns as usize),
+        }
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        io::Read::read(&mut self.tty, buf)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.tty.write_all(bytes)?;
+        self.tty.flush()
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        use std::os::fd::AsRawFd;
+
+        let _ = self.write(b"\x1b[?1049l");
+        // SAFETY: `saved` is the valid `termios` read by `tcgetattr`, which
+        // the call only reads.
+        unsafe { libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSAFLUSH, &self.saved) };
+    }
+}
+
+#[cfg(not(unix))]
+struct Terminal;
+
+#[cfg(not(unix))]
+impl Terminal {
+    fn open() -> io::Result<Terminal> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "--select is only supported on Unix",
+        ))
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (24, 80)
+    }
+
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Ok(0)
+    }
+
+    fn write(&mut self, _bytes: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+//! Searching the objects of S3, or of another object storage offering its
+//! API, given as `s3://bucket/prefix`.
+//!
+//! A prefix stands for every object whose key starts with it, each searched
+//! as a file named `s3://bucket/key`. Requests are signed with AWS Signature
+//! Version 4 using the credentials in `AWS_ACCESS_KEY_ID`,
+//! `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`, or sent anonymously
+//! without them. The region is read from `AWS_REGION` or
+//! `AWS_DEFAULT_REGION`, and `AWS_ENDPOINT_URL` points the requests at
+//! another object storage. It is enabled by the `s3` cargo feature.
+
+use std::io;
+
+/// Maximum number of objects downloaded at the same time.
+pub const IN_FLIGHT: usize = 16;
+
+/// Returns whether `path` names objects in S3.
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("s3://")
+}
+
+/// Returns the objects whose keys start with the prefix in `url`, each named
+/// by its own URL, in order of their keys.
+#[cfg(feature = "s3")]
+pub fn list(url: &str) -> io::Result<Vec<String>> {
+    let (bucket, prefix) = split(url)?;
+    let mut objects = Vec::new();
+    let mut token = None;
+    loop {
+        let mut query = vec![
+            ("list-type", "2".to_string()),
+            ("prefix", prefix.to_string()),
+        ];
+        if let Some(token) = token.take() {
+            query.push(("continuation-token", token));
+        }
+        let xml = get(bucket, "", &mut query)?.into_string()?;
+
+        // Keys ending with a slash are the markers some tools leave for
+        // folders, which hold nothing to search.
+        let keys = elements(&xml, "Key").filter(|key| !key.ends_with('/'));
+        objects.extend(keys.map(|key| format!("s3://{bucket}/{key}")));
+        if elements(&xml, "IsTruncated").next().as_deref() != Some("true") {
+            break;
+        }
+        token = elements(&xml, "NextContinuationToken").next();
+        if token.is_none() {
+            break;
+        }
+    }
+    Ok(objects)
+}
+
+/// Downloads the object at `url`.
+#[cfg(feature = "s3")]
+pub fn fetch(url: &str) -> io::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let (bucket, key) = split(url)?;
+    let mut bytes = Vec::new();
+    get(bucket, key, &mut Vec::new())?
+        .into_reader()
+        .read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(not(feature = "s3"))]
+pub fn list(_url: &str) -> io::Result<Vec<String>> {
+    Err(unsupported())
+}
+
+#[cfg(not(feature = "s3"))]
+pub fn fetch(_url: &str) -> io::Result<Vec<u8>> {
+    Err(unsupported())
+}
+
+#[cfg(not(feature = "s3"))]
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "searching S3 requires the s3 feature",
+    )
+}
+
+/// Splits `url` into its bucket and the key or prefix that follows it.
+#[cfg(feature = "s3")]
+fn split(url: &str) -> io::Result<(&str, &str)> {
+    let path = url.strip_prefix("s3://").unwrap_or(url);
+    let (bucket, key) = path.split_once('/').unwrap_or((path, ""));
+    if bucket.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no bucket given",
+        ));
+    }
+    Ok((bucket, key))
+}
+
+/// Sends a GET request for `key` in `bucket` with the parameters in `query`,
+/// which are sorted as signing requires.
+#[cfg(feature = "s3")]
+fn get(bucket: &str, key: &str, query: &mut [(&str, String)]) -> io::Result<ureq::Response> {
+    use std::env;
+    use std::time::SystemTime;
+
+    let region = env::var("AWS_REGION")
+        .or_else(|_| env::var("AWS_DEFAULT_REGION"))
+        .unwrap_or_else(|_| "us-east-1".to_string());
+    // Other object storages are addressed with the bucket in the path, and
+    // S3 with the bucket in the host name.
+    let (base, path) = match env::var("AWS_ENDPOINT_URL") {
+        Ok(endpoint) => (
+            endpoint.trim_end_matches('/').to_string(),
+            format!("/{bucket}/{}", encode(key, false)),
+        ),
+        Err(_) => (
+            format!("https://{bucket}.s3.{region}.amazonaws.com"),
+            format!("/{}", encode(key, false)),
+        ),
+    };
+    query.sort();
+    let query = query
+        .iter()
+        .map(|(name, value)| format!("{}={}", encode(name, true), encode(value, true)))
+        .collect::<Vec<_>>()
+        .join("&");
+    let url = if query.is_empty() {
+        format!("{base}{path}")
+    } else {
+        format!("{base}{path}?{query}")
+    };
+
+    let mut request = ureq::get(&url);
+    if let Ok(access_key) = env::var("AWS_ACCESS_KEY_ID") {
+        let secret_key = env::var("AWS_SECRET_ACCESS_KEY").unwrap_or_default();
+        let token = env::var("AWS_SESSION_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty());
+        let [year, month, day, hour, minute, second] = crate::time::utc(SystemTime::now());
+        let date = format!("{year:04}{month:02}{day:02}");
+        let date_time = format!("{date}T{hour:02}{minute:02}{second:02}Z");
+        let host = base.split_once("://").map_or(&base[..], |(_, host)| host);
+        let payload = hex(&sha256(b""));
+
+        let mut headers = vec![
+            ("host", host.to_string()),
+            ("x-amz-content-sha256", payload.clone()),
+            ("x-amz-date", date_time.clone()),
+        ];
+        if let Some(token) = token {
+            headers.push(("x-amz-security-token", token));
+        }
+        let signed_headers = headers.iter().map(|(name, _)| *name);
+        let signed_headers = signed_headers.collect::<Vec<_>>().join(";");
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{name}:{value}\n"))
+            .collect();
+        let canonical_request =
+            format!("GET\n{path}\n{query}\n{canonical_headers}\n{signed_headers}\n{payload}");
+
+        let scope = format!("{date}/{region}/s3/aws4_request");
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{date_time}\n{scope}\n{}",
+            hex(&sha256(canonical_request.as_bytes()))
+        );
+        let mut signing_key = format!("AWS4{secret_key}").into_bytes();
+        for part in [&date[..], &region, "s3", "aws4_request"] {
+            signing_key = hmac(&signing_key, part.as_bytes());
+        }
+        let signature = hex(&hmac(&signing_key, string_to_sign.as_bytes()));
+
+        for (name, value) in &headers[1..] {
+            request = request.set(name, value);
+        }
+        request = request.set(
+            "authorization",
+            &format!(
+                "AWS4-HMAC-SHA256 Credential={access_key}/{scope}, \
+                 SignedHeaders={signed_headers}, Signature={signature}"
+            ),
+        );
+    }
+    request.call().map_err(crate::remote::error)
+}
+
+/// Percent-encodes every byte of `s` but the unreserved characters, and the
+/// slashes between the parts of a key unless `slash` is set.
+#[cfg(feature = "s3")]
+fn encode(s: &str, slash: bool) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(b as char)
+            }
+            b'/' if !slash => encoded.push('/'),
+            b => encoded.push_str(&format!("%{b:02X}")),
+        }
+    }
+    encoded
+}
+
+/// Returns the text of every `name` element in `xml`.
+#[cfg(feature = "s3")]
+fn elements<'a>(xml: &'a str, name: &str) -> impl Iterator<Item = String> + 'a {
+    let open = format!("<{name}>");
+    let close = format!("</{name}>");
+    let mut rest = xml;
+    std::iter::from_fn(move || {
+        let start = rest.find(&open)? + open.len();
+        let end = start + rest[start..].find(&close)?;
+        let text = crate::decoder::unescape(&rest[start..end]);
+        rest = &rest[end + close.len()..];
+        Some(text)
+    })
+}
+
+/// Returns the SHA-256 digest of `bytes`.
+#[cfg(feature = "s3")]
+fn sha256(bytes: &[u8]) -> Vec<u8> {
+    use sha2::{Digest, Sha256};
+
+    Sha256::digest(bytes).to_vec()
+}
+
+/// Returns the HMAC-SHA256 of `bytes` under `key`.
+#[cfg(feature = "s3")]
+fn hmac(key: &[u8], bytes: &[u8]) -> Vec<u8> {
+    use hmac::{Hmac, Mac};
+
+    let mut mac = match Hmac::<sha2::Sha256>::new_from_slice(key) {
+        Ok(mac) => mac,
+        Err(e) => panic!("{e}"),
+    };
+    mac.update(bytes);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Formats `bytes` as lowercase hexadecimal digits.
+#[cfg(feature = "s3")]
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
@@ -0,0 +1,254 @@
+//! A long-running server answering search requests with JSON-RPC 2.0.
+//!
+//! Requests and responses are exchanged one per line, either on stdin and
+//! stdout or on the connections accepted by a unix socket. The parameters of a
+//! `search` request are the command line arguments of the search, e.g.
+//!
+//! ```text
+//! {"jsonrpc":"2.0","id":1,"method":"search","params":["-e","TODO","-f","src/main.rs"]}
+//! ```
+//!
+//! and its result is the array of events that `--json` would print, followed
+//! by an `error` event for each file or directory that could not be
+//! searched. The compiled patterns and the ignore files read while expanding
+//! directories are kept between requests, so that repeated queries skip the
+//! cost of building the regex and of parsing ignore files that have not
+//! changed. Each cache holds a bounded number of entries, dropping the least
+//! recently used. The `shutdown` method stops the server.
+//!
+//! Stdin cannot be searched, and the options that only make sense on the
+//! command line, such as --select, --write or --output, are refused.
+
+use crate::cache::Cache;
+use crate::json::{self, Value};
+use crate::walk::IgnoreCache;
+use crate::Cli;
+use clap::Parser;
+use regex::Regex;
+use std::io::{self, BufRead, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+const PARSE_ERROR: i32 = -32700;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+const INTERNAL_ERROR: i32 = -32603;
+
+/// Maximum number of compiled patterns kept between requests.
+const PATTERNS: usize = 64;
+
+/// Maximum number of directories whose ignore files are kept between
+/// requests.
+const IGNORES: usize = 4096;
+
+/// What is kept between requests.
+struct State {
+    patterns: Cache<String, Regex>,
+    ignores: IgnoreCache,
+}
+
+/// Serves requests on the unix socket at `socket`, or on stdin and stdout if
+/// no socket is given, until a `shutdown` request is received.
+pub fn run(socket: Option<&Path>) -> io::Result<()> {
+    let mut cache = State {
+        patterns: Cache::new(PATTERNS),
+        ignores: Cache::new(IGNORES),
+    };
+
+    let Some(socket) = socket else {
+        serve(io::stdin().lock(), &mut io::stdout().lock(), &mut cache)?;
+        return Ok(());
+    };
+
+    #[cfg(unix)]
+    {
+        let listener = std::os::unix::net::UnixListener::bind(socket)?;
+        for stream in listener.incoming() {
+            let mut stream = stream?;
+            let reader = io::BufReader::new(stream.try_clone()?);
+            if serve(reader, &mut stream, &mut cache)? {
+                break;
+            }
+        }
+        std::fs::remove_file(socket)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = cache;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "cannot listen on {}: unix sockets are not supported",
+                socket.display()
+            ),
+        ))
+    }
+}
+
+/// Answers the requests read from `input` until it is exhausted or a
+/// `shutdown` request is received, in which case true is returned.
+fn serve(input: impl BufRead, output: &mut impl Write, cache: &mut State) -> io::Result<bool> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request = match json::parse(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                respond(output, &Value::Null, Err((PARSE_ERROR, e)))?;
+                continue;
+            }
+        };
+
+        let method = match request.get("method") {
+            Some(Value::String(method)) => method.as_str(),
+            _ => "",
+        };
+        let result = match method {
+            "search" => search(request.get("params"), cache),
+            "shutdown" => Ok("null".to_string()),
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method '{method}'"))),
+        };
+
+        // Requests without an id are notifications, which get no response.
+        if let Some(id) = request.get("id") {
+            respond(output, id, result)?;
+        }
+
+        if method == "shutdown" {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+fn respond(
+    output: &mut impl Write,
+    id: &Value,
+    result: Result<String, (i32, String)>,
+) -> io::Result<()> {
+    match result {
+        Ok(result) => writeln!(
+            output,
+            r#"{{"jsonrpc":"2.0","id":{},"result":{result}}}"#,
+            id.to_json()
+        )?,
+        Err((code, message)) => writeln!(
+            output,
+            r#"{{"jsonrpc":"2.0","id":{},"error":{{"code":{code},"message":"{}"}}}}"#,
+            id.to_json(),
+            json::escape(&message)
+        )?,
+    }
+    output.flush()
+}
+
+/// Runs the search described by the command line arguments in `params`,
+/// returning the JSON array of its events.
+fn search(params: Option<&Value>, cache: &mut State) -> Result<String, (i32, String)> {
+    let invalid_params = || {
+        (
+            INVALID_PARAMS,
+            "params must be an array of strings".to_string(),
+        )
+    };
+    let Some(Value::Array(params)) = params else {
+        return Err(invalid_params());
+    };
+
+    let mut argv = vec!["grep".to_string()];
+    for param in params {
+        match param {
+            Value::String(arg) => argv.push(arg.clone()),
+            _ => return Err(invalid_params()),
+        }
+    }
+
+    let cli = Cli::try_parse_from(argv).map_err(|e| (INVALID_PARAMS, e.to_string()))?;
+    let Ok(mut args) = cli.into_search() else {
+        return Err((
+            INVALID_PARAMS,
+            "only the search and replace commands are supported by the server".to_string(),
+        ));
+    };
+    args.json = true;
+    // The files that cannot be searched are reported in the response rather
+    // than on stderr.
+    args.no_messages = true;
+    if args.file.is_empty() {
+        return Err((INVALID_PARAMS, "File name must be provided".to_string()));
+    }
+
+    // Standard input carries the requests, so neither patterns nor text to
+    // search can be read from it.
+    if args.pattern_file.as_deref() == Some("-") {
+        return Err((
+            INVALID_PARAMS,
+            "--pattern-file - is not supported by the server".to_string(),
+        ));
+    }
+    if args.file.iter().any(|file| file == "-") {
+        return Err((
+            INVALID_PARAMS,
+            "searching stdin is not supported by the server".to_string(),
+        ));
+    }
+    let (pattern, patterns) = crate::read_pattern(&args).map_err(|e| (INVALID_PARAMS, e))?;
+    crate::settle(&mut args).map_err(|e| (INVALID_PARAMS, e))?;
+    // Hex dumps have no JSON form, and the response is the only output of a
+    // request, which cannot wait on a terminal or change the files searched.
+    let unsupported = [
+        (args.hexdump, "--hexdump and --hex are"),
+        (args.select, "--select is"),
+        (args.write, "--write is"),
+        (args.output.is_some(), "--output is"),
+        (args.checkpoint.is_some(), "--checkpoint is"),
+        (args.error_log.is_some(), "--error-log is"),
+    ];
+    if let Some((_, options)) = unsupported.iter().find(|(given, _)| *given) {
+        return Err((
+            INVALID_PARAMS,
+            format!("{options} not supported by the server"),
+        ));
+    }
+    let re = match cache.patterns.get(&pattern) {
+        Some(re) => re.clone(),
+        None => {
+            let re = Regex::new(&pattern)
+                .map_err(|e| (INVALID_PARAMS, crate::diagnostic::explain(&pattern, &e)))?;
+            cache.patterns.insert(pattern, re.clone());
+            re
+        }
+    };
+    crate::compile_patterns(&mut args, patterns).map_err(|e| (INVALID_PARAMS, e))?;
+    crate::check_groups(&re, &args).map_err(|e| (INVALID_PARAMS, e))?;
+
+    // Errors while searching are raised as panics, which must not bring the
+    // whole server down.
+    let mut events = Vec::new();
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        let walk_errors = crate::expand_files(&mut args, Some(&mut cache.ignores));
+        let totals = crate::search(&mut events, &re, &args);
+        for (path, e) in walk_errors.iter().chain(&totals.files_failed) {
+            if let Err(e) = json::error(&mut events, path, &e.to_string()) {
+                panic!("{e}");
+            }
+        }
+    }))
+    .map_err(|e| {
+        let message = match (e.downcast_ref::<String>(), e.downcast_ref::<&str>()) {
+            (Some(message), _) => message.clone(),
+            (_, Some(message)) => message.to_string(),
+            _ => "search failed".to_string(),
+        };
+        (INTERNAL_ERROR, message)
+    })?;
+
+    let events = String::from_utf8_lossy(&events);
+    let events: Vec<&str> = events.lines().collect();
+    Ok(format!("[{}]", events.join(",")))
+}
@@ -0,0 +1,135 @@
+//! Search statistics.
+
+use crate::json;
+use clap::ValueEnum;
+use std::ops::AddAssign;
+use std::time::Duration;
+
+/// How --stats prints the statistics.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// A table, one counter per row, followed by one row per worker.
+    Table,
+    /// A single JSON object.
+    Json,
+}
+
+/// Counters collected while searching a file. Statistics for several files
+/// are aggregated with `+=`.
+#[derive(Clone, Copy, Default)]
+pub struct Stats {
+    pub lines_searched: usize,
+    pub matched_lines: usize,
+    pub matches: usize,
+    /// Time spent opening and reading the file.
+    pub read_time: Duration,
+    /// Time spent running the regex over the lines that were read.
+    pub match_time: Duration,
+    /// Time spent printing matches and their context.
+    pub print_time: Duration,
+    /// The number of the last line read and the offset just past it, from
+    /// which --follow-lines carries on. It is not aggregated.
+    pub position: (usize, usize),
+}
+
+/// The statistics of a whole search, printed by --stats.
+pub struct Summary<'a> {
+    pub total: Stats,
+    /// The statistics of the files searched by each worker thread. Printing
+    /// happens on the main thread, so its time is only part of the total.
+    pub workers: &'a [Stats],
+    /// Time spent expanding directories into the files they hold.
+    pub walk_time: Duration,
+    pub files_searched: usize,
+    pub files_matched: usize,
+}
+
+impl Summary<'_> {
+    /// Formats the statistics as a table, one counter per row, followed by
+    /// the lines searched and the time spent reading and matching by each
+    /// worker when there is more than one.
+    pub fn table(&self) -> String {
+        let total = &self.total;
+        let time = self.walk_time + total.read_time + total.match_time + total.print_time;
+        let rows = [
+            ("files searched", self.files_searched.to_string()),
+            ("files matched", self.files_matched.to_string()),
+            ("lines searched", total.lines_searched.to_string()),
+            ("matched lines", total.matched_lines.to_string()),
+            ("matches", total.matches.to_string()),
+            ("time walking", format!("{:?}", self.walk_time)),
+            ("time reading", format!("{:?}", total.read_time)),
+            ("time matching", format!("{:?}", total.match_time)),
+            ("time printing", format!("{:?}", total.print_time)),
+            ("time total", format!("{time:?}")),
+        ];
+
+        let mut table: String = rows
+            .iter()
+            .map(|(name, value)| format!("{name:<16}{value:>16}\n"))
+            .collect();
+        if self.workers.len() > 1 {
+            table.push_str(&format!(
+                "\n{:<8}{:>16}{:>16}{:>16}\n",
+                "worker", "lines", "reading", "matching"
+            ));
+            for (i, stats) in self.workers.iter().enumerate() {
+                table.push_str(&format!(
+                    "{i:<8}{:>16}{:>16}{:>16}\n",
+                    stats.lines_searched,
+                    format!("{:?}", stats.read_time),
+                    format!("{:?}", stats.match_time)
+                ));
+            }
+        }
+        table
+    }
+
+    /// Formats the statistics as a JSON object on a single line.
+    pub fn json(&self) -> String {
+        let workers: Vec<String> = self
+            .workers
+            .iter()
+            .map(|&stats| json::stats_to_json(stats))
+            .collect();
+        format!(
+            r#"{{"files_searched":{},"files_matched":{},"walk":{},"stats":{},"workers":[{}]}}"#,
+            self.files_searched,
+            self.files_matched,
+            self.walk_time.as_secs_f64(),
+            json::stats_to_json(self.total),
+            workers.join(",")
+        )
+    }
+}
+
+/// Formats the matches and matched lines of each of `files`, in the order
+/// given, as a table ending with a row for the `total`.
+pub fn count_table(files: &[(String, Stats)], total: Stats) -> String {
+    let total_row = ("total", total);
+    let rows = files
+        .iter()
+        .map(|(file, stats)| (file.as_str(), *stats))
+        .chain([total_row]);
+    let width = rows.clone().map(|(file, _)| file.len()).max().unwrap_or(0);
+
+    let mut table = format!("{:<width$}  {:>10}  {:>10}\n", "file", "matches", "lines");
+    for (file, stats) in rows {
+        table.push_str(&format!(
+            "{file:<width$}  {:>10}  {:>10}\n",
+            stats.matches, stats.matched_lines
+        ));
+    }
+    table
+}
+
+impl AddAssign for Stats {
+    fn add_assign(&mut self, other: Self) {
+        self.lines_searched += other.lines_searched;
+        self.matched_lines += other.matched_lines;
+        self.matches += other.matches;
+        self.read_time += other.read_time;
+        self.match_time += other.match_time;
+        self.print_time += other.print_time;
+    }
+}
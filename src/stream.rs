@@ -0,0 +1,208 @@
+//! Line-by-line search over a stream of lines.
+//!
+//! Lines are read one at a time into a buffer that is reused for every line,
+//! and matched as raw bytes, so searching a line allocates nothing. Only the
+//! lines selected for printing are copied and converted to UTF-8. The lines
+//! that might still be needed as before context of a later match are held in
+//! a ring buffer of fixed capacity, so the memory used while searching grows
+//! with the amount of context rather than with the size of the file.
+
+use crate::printer::Line;
+use crate::stats::Stats;
+use grep::interval::Interval;
+use grep::lines::LineReader;
+use regex::bytes::Regex;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read};
+use std::time::Instant;
+
+/// Fails every read once a deadline has passed, with an error of kind
+/// `TimedOut`, which stops a `LineReader` as if the file ended there.
+pub struct Deadline<R> {
+    reader: R,
+    deadline: Option<Instant>,
+}
+
+impl<R> Deadline<R> {
+    /// Returns a reader of `reader` that fails once `deadline`, if any, has
+    /// passed.
+    pub fn new(reader: R, deadline: Option<Instant>) -> Deadline<R> {
+        Deadline { reader, deadline }
+    }
+}
+
+impl<R: Read> Read for Deadline<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(timed_out());
+        }
+        self.reader.read(buf)
+    }
+}
+
+/// The error of a file whose search did not complete before its deadline.
+pub fn timed_out() -> io::Error {
+    io::Error::new(ErrorKind::TimedOut, "search timed out")
+}
+
+/// Holds the last `capacity` lines that were not selected, evicting the
+/// oldest when full. The buffers of evicted lines are reused.
+struct RingBuffer {
+    /// The number and offset of each line, along with its bytes.
+    lines: VecDeque<(usize, usize, Vec<u8>)>,
+    capacity: usize,
+}
+
+impl RingBuffer {
+    fn new(capacity: usize) -> RingBuffer {
+        RingBuffer {
+            lines: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    fn push(&mut self, number: usize, offset: usize, line: &[u8]) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut buf = if self.lines.len() == self.capacity {
+            self.lines.pop_front().unwrap().2
+        } else {
+            Vec::new()
+        };
+        buf.clear();
+        buf.extend_from_slice(line);
+        self.lines.push_back((number, offset, buf));
+    }
+
+    /// Removes every line, oldest first.
+    fn drain(&mut self) -> impl Iterator<Item = (usize, usize, Vec<u8>)> + '_ {
+        self.lines.drain(..)
+    }
+}
+
+/// Searches `lines` for `re`. Returns the matching lines with
+/// `before_context` and `after_context` lines of context, in order and
+/// without duplicates, along with the statistics of the search. With a
+/// `max_count`, reading stops after that many matching lines and their after
+/// context.
+pub fn search(
+    re: &Regex,
+    lines: &mut LineReader<impl Read>,
+    before_context: usize,
+    after_context: usize,
+    max_count: Option<usize>,
+) -> (Vec<Line<'static>>, Stats) {
+    let mut selected = Vec::new();
+    let stats = search_each(
+        re,
+        lines,
+        before_context,
+        after_context,
+        max_count,
+        |line| selected.push(line),
+    );
+
+    // Only the last line of the file can lack a newline, and only when
+    // reading reached it.
+    if let Some(line) = selected
+        .last_mut()
+        .filter(|line| line.number == lines.number())
+    {
+        line.terminated = lines.terminated();
+    }
+
+    (selected, stats)
+}
+
+/// Searches `lines` for `re` like `search`, but passes each line to `select`
+/// as soon as it is selected rather than once the search is complete, so
+/// every line is taken as terminated.
+pub fn search_each(
+    re: &Regex,
+    lines: &mut LineReader<impl Read>,
+    before_context: usize,
+    after_context: usize,
+    max_count: Option<usize>,
+    mut select: impl FnMut(Line<'static>),
+) -> Stats {
+    let mut stats = Stats::default();
+
+    // Lines that were not selected, of which only the last few may still
+    // turn out to be before context.
+    let mut before = RingBuffer::new(before_context);
+    // The lines surrounding the last match, which are selected as they are
+    // read.
+    let mut window: Option<Interval<usize>> = None;
+
+    while let Some((number, offset, line)) = lines.next_line() {
+        let limit_reached = max_count.is_some_and(|max| stats.matched_lines >= max);
+        if limit_reached && window.is_none_or(|w| !w.contains(number)) {
+            break;
+        }
+        stats.lines_searched += 1;
+
+        if !limit_reached && re.is_match(line) {
+            stats.matched_lines += 1;
+            stats.matches += re.find_iter(line).count();
+
+            // The buffer only holds lines read since the last one selected,
+            // so none of them is selected twice.
+            let w = Interval::new(number, number).expand(before_context, after_context, None);
+            for (number, offset, line) in before.drain() {
+                if w.contains(number) {
+                    select(line_of(number, offset, line, false));
+                }
+            }
+            window = Some(w);
+
+            select(line_of(number, offset, line.to_vec(), true));
+        } else if window.is_some_and(|w| w.contains(number)) {
+            select(line_of(number, offset, line.to_vec(), false));
+        } else {
+            before.push(number, offset, line);
+        }
+    }
+
+    stats.position = (lines.number(), lines.offset());
+    stats
+}
+
+/// Searches `lines` for the first line matching `re`, without reading any
+/// further.
+pub fn find_first(re: &Regex, lines: &mut LineReader<impl Read>) -> Stats {
+    let mut stats = Stats::default();
+    while let Some((_, _, line)) = lines.next_line() {
+        stats.lines_searched += 1;
+        if re.is_match(line) {
+            stats.matched_lines = 1;
+            stats.matches = 1;
+            break;
+        }
+    }
+    stats
+}
+
+/// Converts the text of a selected line to UTF-8, replacing invalid
+/// sequences.
+pub fn to_string(line: Vec<u8>) -> String {
+    match String::from_utf8(line) {
+        Ok(text) => text,
+        Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+    }
+}
+
+fn line_of(number: usize, offset: usize, line: Vec<u8>, matched: bool) -> Line<'static> {
+    Line {
+        number,
+        offset,
+        text: Cow::Owned(to_string(line)),
+        matched,
+        terminated: true,
+        header: false,
+    }
+}
@@ -0,0 +1,338 @@
+//! Parsing of points in time given on the command line or found in the
+//! lines of a log.
+
+use regex::Regex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Matches ISO 8601 timestamps such as `2024-05-01T12:30:00`, where the time
+/// may also follow a space or be left out.
+pub const ISO_TIMESTAMP: &str = concat!(
+    r"(?<year>\d{4})-(?<month>\d{2})-(?<day>\d{2})",
+    r"(?:[T ](?<hour>\d{2}):(?<minute>\d{2})(?::(?<second>\d{2}))?)?",
+);
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// Parses either a duration relative to now, such as `90m` or `2d`, or a date
+/// of the form `YYYY-MM-DD`, taken as midnight UTC, optionally followed by a
+/// time of the form `THH:MM` or `THH:MM:SS`.
+pub fn parse_time(s: &str) -> Result<SystemTime, String> {
+    if s.contains('-') {
+        return parse_date(s);
+    }
+
+    let duration = parse_duration(s)
+        .map_err(|_| format!("expected a duration such as 2d or a date, found '{s}'"))?;
+    SystemTime::now()
+        .checked_sub(duration)
+        .ok_or_else(|| format!("duration '{s}' is too large"))
+}
+
+/// Parses a duration, a whole number followed by one of the units `ms`, `s`,
+/// `m`, `h`, `d` or `w`.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (count, unit) = match s.strip_suffix("ms") {
+        Some(count) => (count, Duration::from_millis(1)),
+        None => match s.chars().last() {
+            Some('s') => (&s[..s.len() - 1], Duration::from_secs(1)),
+            Some('m') => (&s[..s.len() - 1], Duration::from_secs(60)),
+            Some('h') => (&s[..s.len() - 1], Duration::from_secs(60 * 60)),
+            Some('d') => (&s[..s.len() - 1], Duration::from_secs(SECONDS_PER_DAY)),
+            Some('w') => (&s[..s.len() - 1], Duration::from_secs(7 * SECONDS_PER_DAY)),
+            _ => return Err(format!("expected a duration such as 30s, found '{s}'")),
+        },
+    };
+
+    let count: u32 = count
+        .parse()
+        .map_err(|e| format!("invalid duration '{s}': {e}"))?;
+    unit.checked_mul(count)
+        .ok_or_else(|| format!("duration '{s}' is too large"))
+}
+
+/// Parses a `YYYY-MM-DD` date as midnight UTC, or a `YYYY-MM-DDTHH:MM` or
+/// `YYYY-MM-DDTHH:MM:SS` date and time in UTC.
+fn parse_date(s: &str) -> Result<SystemTime, String> {
+    let invalid = || {
+        format!("expected a date of the form YYYY-MM-DD, YYYY-MM-DDTHH:MM or YYYY-MM-DDTHH:MM:SS, found '{s}'")
+    };
+
+    let (date, time) = s.split_once('T').unwrap_or((s, "00:00"));
+    let fields = |s: &str, separator| -> Result<Vec<u64>, String> {
+        s.split(separator)
+            .map(|field| field.parse().map_err(|_| invalid()))
+            .collect()
+    };
+    let (date, mut time) = (fields(date, '-')?, fields(time, ':')?);
+    // The seconds may be left out.
+    if time.len() == 2 {
+        time.push(0);
+    }
+    if date.len() != 3 || time.len() != 3 {
+        return Err(invalid());
+    }
+
+    datetime(date[0], date[1], date[2], time[0], time[1], time[2]).ok_or_else(invalid)
+}
+
+/// Checks that `pattern` is a valid regular expression with the groups
+/// `line_time` needs to find a timestamp, and compiles it.
+pub fn parse_timestamp_pattern(pattern: &str) -> Result<Regex, String> {
+    let re = Regex::new(pattern).map_err(|e| e.to_string())?;
+    let has = |group| re.capture_names().any(|name| name == Some(group));
+    let date = has("year") && has("month") && has("day");
+    if !date && !has("epoch") {
+        return Err("expected the groups year, month and day, or the group epoch".to_string());
+    }
+    Ok(re)
+}
+
+/// Returns the time of the first timestamp matching `re` in `line`, taken as
+/// UTC. The timestamp is made of the groups `year`, `month`, given as a
+/// number or an English name, `day` and optionally `hour`, `minute` and
+/// `second`, or else of the group `epoch` holding seconds since 1970.
+/// Returns `None` when the line has no valid timestamp.
+pub fn line_time(re: &Regex, line: &str) -> Option<SystemTime> {
+    let caps = re.captures(line)?;
+    if let Some(epoch) = caps.name("epoch") {
+        let seconds = epoch.as_str().split('.').next()?.parse().ok()?;
+        return Some(UNIX_EPOCH + Duration::from_secs(seconds));
+    }
+
+    let number = |group: &str| match caps.name(group) {
+        Some(m) => m.as_str().parse().ok(),
+        None => Some(0),
+    };
+    let month = caps.name("month")?.as_str();
+    let month = month.parse().ok().or_else(|| {
+        let name = month.get(..3)?.to_ascii_lowercase();
+        MONTHS.iter().position(|&m| m == name).map(|i| i as u64 + 1)
+    })?;
+    datetime(
+        caps.name("year")?.as_str().parse().ok()?,
+        month,
+        caps.name("day")?.as_str().parse().ok()?,
+        number("hour")?,
+        number("minute")?,
+        number("second")?,
+    )
+}
+
+/// Returns the given date and time in UTC, or `None` when it is not valid or
+/// is before 1970.
+fn datetime(
+    year: u64,
+    month: u64,
+    day: u64,
+    hour: u64,
+    minute: u64,
+    second: u64,
+) -> Option<SystemTime> {
+    if year < 1970 || !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day)
+    {
+        return None;
+    }
+    // A leap second is allowed.
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let days = days_since_epoch(year, month, day);
+    let seconds = days * SECONDS_PER_DAY + hour * 60 * 60 + minute * 60 + second;
+    Some(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+/// Returns the year, month, day, hour, minute and second of `t` in UTC.
+#[cfg(feature = "s3")]
+pub fn utc(t: SystemTime) -> [u64; 6] {
+    let seconds = t.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, seconds) = (seconds / SECONDS_PER_DAY, seconds % SECONDS_PER_DAY);
+
+    // The inverse of days_since_epoch.
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = (month_from_march + 2) % 12 + 1;
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+    [
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+    ]
+}
+
+/// Returns the number of days in `month` of `year`.
+fn days_in_month(year: u64, month: u64) -> u64 {
+    let leap = year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Returns the number of days from 1970-01-01 to the given date in the
+/// proleptic Gregorian calendar.
+fn days_since_epoch(year: u64, month: u64, day: u64) -> u64 {
+    // Count years from March so that the leap day falls at the end.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    // 719468 is the number of days from 0000-03-01 to 1970-01-01.
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the number of seconds from the epoch to `t`.
+    fn seconds(t: SystemTime) -> u64 {
+        t.duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    #[test]
+    fn days_since_epoch_known_dates() {
+        assert_eq!(days_since_epoch(1970, 1, 1), 0);
+        assert_eq!(days_since_epoch(1970, 3, 1), 59);
+        assert_eq!(days_since_epoch(1972, 2, 29), 789);
+        assert_eq!(days_since_epoch(1972, 3, 1), 790);
+        assert_eq!(days_since_epoch(2000, 1, 1), 10957);
+        // 2000 is a leap year, being divisible by 400.
+        assert_eq!(
+            days_since_epoch(2000, 3, 1) - days_since_epoch(2000, 2, 28),
+            2
+        );
+        // 2100 is not, being divisible by 100 only.
+        assert_eq!(
+            days_since_epoch(2100, 3, 1) - days_since_epoch(2100, 2, 28),
+            1
+        );
+        assert_eq!(days_since_epoch(2024, 5, 1), 19844);
+    }
+
+    #[test]
+    fn parse_dates() {
+        assert_eq!(parse_time("1970-01-01"), Ok(UNIX_EPOCH));
+        assert_eq!(seconds(parse_time("2024-05-01").unwrap()), 1714521600);
+        assert_eq!(
+            seconds(parse_time("2024-05-01T12:30:15").unwrap()),
+            1714566615
+        );
+        assert_eq!(seconds(parse_time("2024-05-01T12:30").unwrap()), 1714566600);
+        assert_eq!(seconds(parse_time("2000-02-29").unwrap()), 951782400);
+    }
+
+    #[test]
+    fn rejects_invalid_dates() {
+        for s in [
+            "1969-12-31",
+            "2024-13-01",
+            "2024-00-10",
+            "2024-04-31",
+            "2023-02-29",
+            "2100-02-29",
+            "2024-05-01T24:00",
+            "2024-05-01T12:60",
+            "2024-05-01T12",
+            "2024-05-01T12:30:15:00",
+            "2024-05",
+            "2024-05-01-02",
+            "2024-May-01",
+            "2024-05-01 12:30",
+        ] {
+            assert!(parse_time(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("90m"), Ok(Duration::from_secs(90 * 60)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(2 * 60 * 60)));
+        assert_eq!(
+            parse_duration("1d"),
+            Ok(Duration::from_secs(SECONDS_PER_DAY))
+        );
+        assert_eq!(
+            parse_duration("2w"),
+            Ok(Duration::from_secs(14 * SECONDS_PER_DAY))
+        );
+        for s in ["", "5", "s", "1.5h", "-1d", "3y", "ms"] {
+            assert!(parse_duration(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn relative_times() {
+        let before = SystemTime::now() - Duration::from_secs(2 * 60 * 60);
+        let t = parse_time("2h").unwrap();
+        assert!(t >= before && t <= SystemTime::now());
+        assert!(parse_time("2x").is_err());
+    }
+
+    #[test]
+    fn line_times() {
+        let iso = Regex::new(ISO_TIMESTAMP).unwrap();
+        let t = line_time(&iso, "[2024-05-01 12:30:15] started").unwrap();
+        assert_eq!(seconds(t), 1714566615);
+        let t = line_time(&iso, "on 2024-05-01T12:30 it failed").unwrap();
+        assert_eq!(seconds(t), 1714566600);
+        assert_eq!(line_time(&iso, "no timestamp"), None);
+        assert_eq!(line_time(&iso, "2024-02-30 is not a date"), None);
+
+        let syslog = parse_timestamp_pattern(
+            r"(?<month>[A-Z][a-z]+) (?<day>\d+) (?<hour>\d\d):(?<minute>\d\d) (?<year>\d{4})",
+        )
+        .unwrap();
+        let t = line_time(&syslog, "May 1 12:30 2024 kernel: oops").unwrap();
+        assert_eq!(seconds(t), 1714566600);
+        let t = line_time(&syslog, "September 9 00:00 2001").unwrap();
+        assert_eq!(seconds(t), 999993600);
+        assert_eq!(line_time(&syslog, "Foo 1 12:30 2024"), None);
+
+        let epoch = parse_timestamp_pattern(r"ts=(?<epoch>[\d.]+)").unwrap();
+        let t = line_time(&epoch, "ts=1714566615.25 ok").unwrap();
+        assert_eq!(seconds(t), 1714566615);
+    }
+
+    #[test]
+    fn timestamp_patterns_need_their_groups() {
+        assert!(parse_timestamp_pattern(r"(?<year>\d{4})-(?<month>\d\d)").is_err());
+        assert!(parse_timestamp_pattern("(").is_err());
+        assert!(parse_timestamp_pattern(r"(?<epoch>\d+)").is_ok());
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn utc_round_trip() {
+        for [year, month, day, hour, minute, second] in [
+            [1970, 1, 1, 0, 0, 0],
+            [2000, 2, 29, 23, 59, 59],
+            [2024, 5, 1, 12, 30, 15],
+            [2100, 3, 1, 6, 0, 0],
+        ] {
+            let t = datetime(year, month, day, hour, minute, second).unwrap();
+            assert_eq!(utc(t), [year, month, day, hour, minute, second]);
+        }
+    }
+}
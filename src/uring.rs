@@ -0,0 +1,178 @@
+//! Reading files through io_uring on Linux.
+//!
+//! Rather than issuing a blocking read syscall per buffer, `UringReader`
+//! queues reads of several consecutive blocks of the file in the kernel and
+//! submits them as a batch. It is enabled by the `io_uring` cargo feature,
+//! and files are read the usual way when io_uring is unavailable, as on
+//! older kernels or where it is disabled.
+
+use io_uring::{opcode, types, IoUring};
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom};
+use std::os::fd::AsRawFd;
+
+/// Size of each block read.
+const BLOCK_SIZE: usize = 256 * 1024;
+/// Number of blocks read in a batch.
+const QUEUE_DEPTH: usize = 8;
+
+/// A file read in batches of blocks queued with io_uring.
+pub struct UringReader {
+    file: File,
+    ring: IoUring,
+    blocks: Vec<Vec<u8>>,
+    /// Number of bytes read into each block of the current batch.
+    lens: [usize; QUEUE_DEPTH],
+    /// Number of blocks of the current batch holding consecutive bytes of the
+    /// file. A short read ends the batch, since the blocks after it do not
+    /// follow on from it.
+    filled: usize,
+    /// Offset in the file of the first block of the current batch.
+    batch_start: u64,
+    /// Position of the next byte to return, as the index of its block and its
+    /// offset within it.
+    block: usize,
+    consumed: usize,
+    eof: bool,
+}
+
+/// Opens `file` for reading through io_uring, or returns it unchanged when
+/// io_uring is unavailable.
+pub fn open(file: File) -> Result<UringReader, File> {
+    match IoUring::new(QUEUE_DEPTH as u32) {
+        Ok(ring) => Ok(UringReader {
+            file,
+            ring,
+            blocks: vec![vec![0; BLOCK_SIZE]; QUEUE_DEPTH],
+            lens: [0; QUEUE_DEPTH],
+            filled: 0,
+            batch_start: 0,
+            block: 0,
+            consumed: 0,
+            eof: false,
+        }),
+        Err(_) => Err(file),
+    }
+}
+
+impl UringReader {
+    /// Returns the file being read.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Returns the offset in the file of the next byte to return.
+    fn position(&self) -> u64 {
+        let before: usize = self.lens[..self.block.min(self.filled)].iter().sum();
+        self.batch_start + (before + self.consumed) as u64
+    }
+
+    /// Reads the batch of blocks starting at `offset`, waiting for every read
+    /// to complete.
+    fn fill(&mut self, offset: u64) -> io::Result<()> {
+        // Until the reads complete, the batch is empty and starts at
+        // `offset`, so that a failed batch is read again.
+        self.lens = [0; QUEUE_DEPTH];
+        self.filled = 0;
+        self.batch_start = offset;
+        self.block = 0;
+        self.consumed = 0;
+
+        let fd = types::Fd(self.file.as_raw_fd());
+        for (i, block) in self.blocks.iter_mut().enumerate() {
+            let read = opcode::Read::new(fd, block.as_mut_ptr(), BLOCK_SIZE as u32)
+                .offset(offset + (i * BLOCK_SIZE) as u64)
+                .build()
+                .user_data(i as u64);
+            // SAFETY: the block outlives the read, which completes before
+            // this function returns, and the ring has room for every block.
+            if unsafe { self.ring.submission().push(&read) }.is_err() {
+                return Err(io::Error::other("io_uring submission queue is full"));
+            }
+        }
+        loop {
+            match self.ring.submit_and_wait(QUEUE_DEPTH) {
+                Ok(_) => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut result = Ok(());
+        for completion in self.ring.completion() {
+            let i = completion.user_data() as usize;
+            match usize::try_from(completion.result()) {
+                Ok(len) => self.lens[i] = len,
+                Err(_) => result = Err(io::Error::from_raw_os_error(-completion.result())),
+            }
+        }
+        result?;
+
+        self.filled = self.lens[..QUEUE_DEPTH - 1]
+            .iter()
+            .position(|&len| len < BLOCK_SIZE)
+            .map_or(QUEUE_DEPTH, |i| i + 1);
+        self.eof = self.lens[0] == 0;
+        Ok(())
+    }
+}
+
+impl Read for UringReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.block < self.filled {
+                let data = &self.blocks[self.block][self.consumed..self.lens[self.block]];
+                if data.is_empty() {
+                    self.block += 1;
+                    self.consumed = 0;
+                    continue;
+                }
+                let n = data.len().min(buf.len());
+                buf[..n].copy_from_slice(&data[..n]);
+                self.consumed += n;
+                return Ok(n);
+            }
+            if self.eof {
+                return Ok(0);
+            }
+            self.fill(self.position())?;
+        }
+    }
+}
+
+impl Seek for UringReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => self.position().checked_add_signed(delta),
+            SeekFrom::End(delta) => self.file.metadata()?.len().checked_add_signed(delta),
+        };
+        let Some(target) = target else {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            ));
+        };
+
+        // Seeking within the current batch keeps the blocks already read,
+        // such as after the start of the file is checked for magic bytes.
+        let mut start = self.batch_start;
+        for (i, &len) in self.lens[..self.filled].iter().enumerate() {
+            if (start..start + len as u64).contains(&target) {
+                self.block = i;
+                self.consumed = (target - start) as usize;
+                return Ok(target);
+            }
+            start += len as u64;
+        }
+
+        // Otherwise the next read starts a new batch at the target.
+        self.lens = [0; QUEUE_DEPTH];
+        self.filled = 0;
+        self.batch_start = target;
+        self.block = 0;
+        self.consumed = 0;
+        self.eof = false;
+        Ok(target)
+    }
+}
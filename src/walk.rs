@@ -0,0 +1,311 @@
+//! Expansion of the files given on the command line.
+//!
+//! Directories and special files, such as FIFOs, sockets and device nodes,
+//! are handled as requested with --directories and --devices rather than
+//! opened like regular files, where reading them could fail or block forever.
+//!
+//! The files found by recursing into a directory are filtered by the
+//! `.gitignore` and `.ignore` files of that directory, of the directories
+//! below it and of its parents up to the root of its git repository, as far
+//! as --no-ignore and its variants allow. The `.git` directories are left out
+//! along with the files hidden by `.gitignore` files.
+//! The server keeps the ignore files it has read in an `IgnoreCache`, so that
+//! they are only read again once they change.
+
+use crate::cache::Cache;
+use crate::help;
+use clap::ValueEnum;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// What to do with a directory in the list of files.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DirectoryAction {
+    /// Read it like a file, which fails.
+    Read,
+    /// Search every file below it.
+    Recurse,
+    /// Leave it out.
+    Skip,
+}
+
+/// What to do with a FIFO, socket or device node in the list of files.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DeviceAction {
+    /// Read it like a file.
+    Read,
+    /// Leave it out.
+    Skip,
+}
+
+/// Which ignore files hide the files found by recursing into a directory.
+#[derive(Clone, Copy, clap::Args)]
+#[command(about = None)]
+pub struct IgnoreOptions {
+    /// Search the files hidden by ignore files of any kind.
+    #[arg(long, help_heading = help::FILTERING)]
+    no_ignore: bool,
+
+    /// Search the files hidden by `.gitignore` files, and those in `.git`
+    /// directories.
+    #[arg(long, help_heading = help::FILTERING)]
+    no_ignore_vcs: bool,
+
+    /// Search the files hidden by `.ignore` files.
+    #[arg(long, help_heading = help::FILTERING)]
+    no_ignore_dot: bool,
+
+    /// Search the files hidden by ignore files in the parents of the
+    /// directories given, rather than in those directories and below.
+    #[arg(long, help_heading = help::FILTERING)]
+    no_ignore_parent: bool,
+}
+
+impl IgnoreOptions {
+    /// Returns the names of the ignore files to read, each taking precedence
+    /// over those before it.
+    fn names(&self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        if !self.no_ignore && !self.no_ignore_vcs {
+            names.push(".gitignore");
+        }
+        if !self.no_ignore && !self.no_ignore_dot {
+            names.push(".ignore");
+        }
+        names
+    }
+}
+
+/// The ignore files read in each directory, by the directory and the names
+/// of the files read, along with the modification time of each of them.
+pub type IgnoreCache = Cache<(PathBuf, Vec<&'static str>), (Vec<Option<SystemTime>>, Gitignore)>;
+
+/// Returns the files to search for the `paths` given, applying `directories`
+/// and `devices`. Files found by recursing into a directory are listed in
+/// order of their names, leaving out those hidden by the ignore files allowed
+/// by `ignore`, and symbolic links among them are not followed. A
+/// file reached through several paths is only listed the first time. Paths
+/// that cannot be inspected are kept, so that searching them reports the
+/// error, while the directories that cannot be read are returned along with
+/// the error. Ignore files are looked up in `cache` before being read.
+pub fn expand(
+    paths: &[String],
+    directories: DirectoryAction,
+    devices: DeviceAction,
+    ignore: IgnoreOptions,
+    mut cache: Option<&mut IgnoreCache>,
+) -> (Vec<String>, Vec<(String, io::Error)>) {
+    let mut files = Files {
+        paths: Vec::with_capacity(paths.len()),
+        seen: HashSet::new(),
+        errors: Vec::new(),
+    };
+    for path in paths {
+        match fs::metadata(path) {
+            Ok(metadata) if metadata.is_dir() => match directories {
+                DirectoryAction::Read => files.add(path, Some(&metadata)),
+                DirectoryAction::Recurse => {
+                    // Ignore files are matched against absolute paths, which
+                    // their parents' also apply to.
+                    let dir = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+                    let mut walker = Walker {
+                        devices,
+                        names: ignore.names(),
+                        ignores: Vec::new(),
+                        cache: cache.as_deref_mut(),
+                    };
+                    if !ignore.no_ignore_parent {
+                        let mut parents = parents(&dir);
+                        parents.reverse();
+                        for parent in parents {
+                            let ignores = walker.read_ignores(parent);
+                            walker.ignores.extend(ignores);
+                        }
+                    }
+                    walker.walk(path, &dir, &mut files);
+                }
+                DirectoryAction::Skip => {}
+            },
+            Ok(metadata) if !metadata.is_file() && devices == DeviceAction::Skip => {}
+            metadata => files.add(path, metadata.ok().as_ref()),
+        }
+    }
+    (files.paths, files.errors)
+}
+
+/// Returns the parents of the directory `dir` whose ignore files apply to it,
+/// the nearest first. They stop at the root of the git repository holding
+/// `dir`, if any.
+fn parents(dir: &Path) -> Vec<&Path> {
+    let mut parents = Vec::new();
+    for dir in dir.ancestors() {
+        if dir.join(".git").exists() {
+            break;
+        }
+        if let Some(parent) = dir.parent() {
+            parents.push(parent);
+        }
+    }
+    parents
+}
+
+/// The files found so far, each listed once however many paths lead to it.
+struct Files {
+    paths: Vec<String>,
+    seen: HashSet<FileId>,
+    /// Directories that could not be read.
+    errors: Vec<(String, io::Error)>,
+}
+
+impl Files {
+    /// Adds `path`, unless the file described by `metadata` was already
+    /// added through another path.
+    fn add(&mut self, path: &str, metadata: Option<&fs::Metadata>) {
+        if let Some(id) = metadata.and_then(|metadata| file_id(path, metadata)) {
+            if !self.seen.insert(id) {
+                return;
+            }
+        }
+        self.paths.push(path.to_string());
+    }
+}
+
+/// Identifies a file independently of the path it is reached through.
+#[cfg(unix)]
+type FileId = (u64, u64);
+
+#[cfg(unix)]
+fn file_id(_path: &str, metadata: &fs::Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+type FileId = std::path::PathBuf;
+
+#[cfg(not(unix))]
+fn file_id(path: &str, _metadata: &fs::Metadata) -> Option<FileId> {
+    fs::canonicalize(path).ok()
+}
+
+/// Recursion into a directory, keeping track of the ignore files that apply
+/// to the directory being read.
+struct Walker<'a> {
+    devices: DeviceAction,
+    /// Names of the ignore files read in every directory.
+    names: Vec<&'static str>,
+    /// The ignore files of the directories above the one being read, the
+    /// innermost last.
+    ignores: Vec<Gitignore>,
+    cache: Option<&'a mut IgnoreCache>,
+}
+
+impl Walker<'_> {
+    /// Adds the files below the directory `dir`, whose absolute path is
+    /// `abs`, to `files`.
+    fn walk(&mut self, dir: &str, abs: &Path, files: &mut Files) {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                files.errors.push((dir.to_string(), e));
+                return;
+            }
+        };
+        let mut entries: Vec<_> = entries.filter_map(Result::ok).collect();
+        entries.sort_by_key(|entry| entry.file_name());
+
+        let depth = self.ignores.len();
+        let ignores = self.read_ignores(abs);
+        self.ignores.extend(ignores);
+
+        for entry in entries {
+            let path = entry.path().to_string_lossy().into_owned();
+            let abs = abs.join(entry.file_name());
+            // Unlike `fs::metadata`, this does not follow symbolic links.
+            let Ok(metadata) = entry.metadata() else {
+                files.add(&path, None);
+                continue;
+            };
+            let file_type = metadata.file_type();
+            // The repository itself is hidden along with the files git
+            // ignores.
+            let is_repository = file_type.is_dir() && entry.file_name() == ".git";
+            if is_repository && self.names.contains(&".gitignore") {
+                continue;
+            }
+            if self.is_ignored(&abs, file_type.is_dir()) {
+                continue;
+            }
+            let is_device = !file_type.is_file() && !file_type.is_symlink();
+            if file_type.is_dir() {
+                self.walk(&path, &abs, files);
+            } else if file_type.is_file() || (is_device && self.devices == DeviceAction::Read) {
+                files.add(&path, Some(&metadata));
+            }
+        }
+
+        self.ignores.truncate(depth);
+    }
+
+    /// Returns whether the file at the absolute path `abs` is hidden by an
+    /// ignore file. Those of inner directories take precedence, so that they
+    /// can bring back a file ignored further up.
+    fn is_ignored(&self, abs: &Path, is_dir: bool) -> bool {
+        for ignore in self.ignores.iter().rev() {
+            match ignore.matched(abs, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+
+    /// Returns the ignore files of the directory `dir`, from the cache while
+    /// they are unchanged.
+    fn read_ignores(&mut self, dir: &Path) -> Option<Gitignore> {
+        let Some(cache) = self.cache.as_deref_mut() else {
+            return read_ignores(dir, &self.names);
+        };
+        let modified: Vec<Option<SystemTime>> = self
+            .names
+            .iter()
+            .map(|name| fs::metadata(dir.join(name)).and_then(|m| m.modified()).ok())
+            .collect();
+        if modified.iter().all(Option::is_none) {
+            return None;
+        }
+        let key = (dir.to_path_buf(), self.names.clone());
+        if let Some((cached, ignore)) = cache.get(&key) {
+            if *cached == modified {
+                return Some(ignore.clone());
+            }
+        }
+        let ignore = read_ignores(dir, &self.names)?;
+        cache.insert(key, (modified, ignore.clone()));
+        Some(ignore)
+    }
+}
+
+/// Reads the ignore files called `names` in the directory `dir`, the later
+/// taking precedence. Returns `None` when there are none. Invalid patterns
+/// are left out rather than failing the search.
+fn read_ignores(dir: &Path, names: &[&str]) -> Option<Gitignore> {
+    let mut builder = GitignoreBuilder::new(dir);
+    let mut found = false;
+    for name in names {
+        let path = dir.join(name);
+        if path.is_file() {
+            found = true;
+            let _ = builder.add(path);
+        }
+    }
+    let ignore = builder.build().ok()?;
+    (found && !ignore.is_empty()).then_some(ignore)
+}